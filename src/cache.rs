@@ -0,0 +1,173 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const CACHE_DIR: &'static str = ".cache/mav/downloads";
+
+/// A verified download kept in the cache, stored as `<key>` next to its `<key>.toml`, where the
+/// key is `<program>-<version>`. The entry records where the download came from and the digest
+/// it was verified against, so that it can be restored without going online.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(skip)]
+    pub key: String,
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch.
+    pub stored: u64,
+    /// Seconds since the Unix epoch.
+    pub used: u64,
+}
+
+pub fn dir() -> io::Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(CACHE_DIR))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "cannot find the home directory"))
+}
+
+pub fn sha256_of(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.result()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn read_entry(dir: &Path, key: &str) -> io::Result<Entry> {
+    let mut content = String::new();
+    fs::File::open(dir.join(format!("{}.toml", key)))?.read_to_string(&mut content)?;
+
+    let entry: Entry =
+        toml::from_str(&content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    Ok(Entry {
+        key: key.into(),
+        ..entry
+    })
+}
+
+fn write_entry(dir: &Path, entry: &Entry) -> io::Result<()> {
+    let content = toml::to_string(entry).map_err(io::Error::other)?;
+    let path = dir.join(format!("{}.toml", entry.key));
+    let temp = dir.join(format!("{}.toml.tmp", entry.key));
+
+    fs::write(&temp, content)?;
+    fs::rename(temp, path)
+}
+
+/// Copies the download cached under `key` to `dest` if it came from `url`, is still intact, and
+/// has the digest pinned for it if any. Returns the digest it was verified against.
+pub fn restore(key: &str, url: &str, pinned: Option<&str>, dest: &Path) -> Option<String> {
+    let restored = || -> io::Result<Option<String>> {
+        let dir = dir()?;
+        let entry = read_entry(&dir, key)?;
+        let blob = dir.join(&entry.key);
+
+        let pinned_otherwise = match pinned {
+            Some(pinned) => pinned != entry.sha256,
+            None => false,
+        };
+
+        if entry.url != url || pinned_otherwise {
+            return Ok(None);
+        }
+
+        if sha256_of(&blob)? != entry.sha256 {
+            remove(&entry)?;
+            return Ok(None);
+        }
+
+        fs::copy(blob, dest)?;
+        write_entry(
+            &dir,
+            &Entry {
+                used: now(),
+                ..entry.clone()
+            },
+        )?;
+
+        Ok(Some(entry.sha256))
+    };
+
+    restored().unwrap_or(None)
+}
+
+/// Keeps a copy of a download whose digest has been verified, under a key naming the program and
+/// its version, replacing what was cached for them.
+pub fn store(key: &str, url: &str, path: &Path, sha256: &str) -> io::Result<()> {
+    let dir = dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let key = key.to_owned();
+    let temp = dir.join(format!("{}.tmp", key));
+
+    fs::copy(path, &temp)?;
+    fs::rename(&temp, dir.join(&key))?;
+
+    write_entry(
+        &dir,
+        &Entry {
+            key,
+            url: url.into(),
+            sha256: sha256.into(),
+            size: fs::metadata(path)?.len(),
+            stored: now(),
+            used: now(),
+        },
+    )
+}
+
+/// Lists cached downloads, least recently used first.
+pub fn entries() -> io::Result<Vec<Entry>> {
+    let dir = dir()?;
+    let mut entries = vec![];
+
+    if !dir.is_dir() {
+        return Ok(entries);
+    }
+
+    for file in fs::read_dir(&dir)? {
+        let name = file?.file_name().to_string_lossy().into_owned();
+
+        // Unreadable entries are skipped, just as `restore` treats them as misses.
+        if name.ends_with(".toml") {
+            if let Ok(entry) = read_entry(&dir, name.trim_end_matches(".toml")) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.used);
+    Ok(entries)
+}
+
+pub fn remove(entry: &Entry) -> io::Result<()> {
+    let dir = dir()?;
+
+    for path in &[
+        dir.join(&entry.key),
+        dir.join(format!("{}.toml", entry.key)),
+    ] {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// How long ago an entry was last used, in whole days.
+pub fn days_unused(entry: &Entry) -> u64 {
+    now().saturating_sub(entry.used) / (24 * 60 * 60)
+}
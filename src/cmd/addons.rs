@@ -0,0 +1,152 @@
+use clap::Arg;
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+
+use super::runner;
+use crate::config::Config;
+use crate::error::Result;
+use crate::util::{self, Executor};
+
+fn name_arg<'a, 'b>(help: &'static str) -> Arg<'a, 'b> {
+    Arg::with_name("name").required(true).help(help)
+}
+
+pub fn list<'a>() -> Command<'a, Config> {
+    Command::new("list")
+        .description("Lists the addons of the Minikube machine")
+        .runner(runner(|config: &Config, _matches, exec| {
+            config.require_env("dev")?;
+
+            let configured = &config.minikube.addons;
+
+            for (name, enabled) in util::get_minikube_addons(exec, &config.minikube.profile)? {
+                let state = if enabled { "enabled" } else { "disabled" };
+
+                match configured.get(&name) {
+                    Some(&wanted) if wanted != enabled => println!(
+                        "{:<24} {} {}",
+                        name,
+                        state.yellow(),
+                        "differs from the config, run `mav addons sync`".yellow(),
+                    ),
+                    Some(_) => println!("{:<24} {} (configured)", name, state.green()),
+                    None => println!("{:<24} {}", name, state),
+                }
+            }
+
+            Ok(())
+        }))
+}
+
+pub fn enable<'a>() -> Command<'a, Config> {
+    Command::new("enable")
+        .description("Enables an addon until the next sync")
+        .options(|app| app.arg(name_arg("Sets the addon to enable")))
+        .runner(runner(|config: &Config, matches, exec| {
+            config.require_env("dev")?;
+
+            let name = matches.value_of("name").unwrap();
+            util::Command::minikube(&config.minikube.profile, vec!["addons", "enable", name])
+                .run(exec)
+        }))
+}
+
+pub fn disable<'a>() -> Command<'a, Config> {
+    Command::new("disable")
+        .description("Disables an addon until the next sync")
+        .options(|app| app.arg(name_arg("Sets the addon to disable")))
+        .runner(runner(|config: &Config, matches, exec| {
+            config.require_env("dev")?;
+
+            let name = matches.value_of("name").unwrap();
+            util::Command::minikube(&config.minikube.profile, vec!["addons", "disable", name])
+                .run(exec)
+        }))
+}
+
+pub fn sync_cmd<'a>() -> Command<'a, Config> {
+    Command::new("sync")
+        .description("Enables and disables addons as `minikube.addons` in the config says")
+        .runner(runner(|config: &Config, _matches, exec| {
+            config.require_env("dev")?;
+            sync(config, exec)
+        }))
+}
+
+/// Brings the addons of the machine in line with the config, reporting what changed.
+pub fn sync(config: &Config, exec: &dyn Executor) -> Result<()> {
+    let profile = &config.minikube.profile;
+    let current = util::get_minikube_addons(exec, profile)?;
+    let mut changed = false;
+
+    for (name, &wanted) in &config.minikube.addons {
+        if current.get(name) == Some(&wanted) {
+            continue;
+        }
+
+        let (action, report) = if wanted {
+            ("enable", "Enabling addon:")
+        } else {
+            ("disable", "Disabling addon:")
+        };
+
+        println!("{} {}", report.yellow(), name);
+        util::Command::minikube(profile, vec!["addons", action, name]).run(exec)?;
+
+        changed = true;
+    }
+
+    if !changed {
+        println!("{}", "Addons are already in sync.".green());
+    }
+
+    Ok(())
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .add_cmd(list())
+        .add_cmd(enable())
+        .add_cmd(disable())
+        .add_cmd(sync_cmd())
+        .into_cmd(file_stem!(), "Manages the addons of the Minikube machine")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::ScriptedExecutor;
+
+    #[test]
+    fn changes_only_configured_addons_that_differ() {
+        let mut config = Config::default();
+
+        config.minikube.addons = vec![
+            ("ingress".to_string(), true),
+            ("registry".to_string(), true),
+            ("dashboard".to_string(), false),
+        ]
+        .into_iter()
+        .collect();
+
+        let exec = ScriptedExecutor::new()
+            .answer("minikube --profile=mav addons list --output=json", 1, "")
+            .answer(
+                "minikube --profile=mav addons list",
+                0,
+                "- dashboard: enabled\n- ingress: disabled\n- registry: enabled\n- storage-provisioner: enabled",
+            );
+
+        sync(&config, &exec).unwrap();
+
+        assert_eq!(
+            *exec.calls.borrow(),
+            vec![
+                "minikube --profile=mav addons list --output=json",
+                "minikube --profile=mav addons list",
+                "minikube --profile=mav addons disable dashboard",
+                "minikube --profile=mav addons enable ingress",
+            ],
+        );
+    }
+}
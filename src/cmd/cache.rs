@@ -0,0 +1,84 @@
+use clap::Arg;
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+use indicatif::HumanBytes;
+
+use super::runner;
+use crate::cache;
+use crate::config::Config;
+use crate::util;
+
+/// Entries unused for longer than this are pruned without `--all`.
+const PRUNE_AFTER_DAYS: u64 = 30;
+
+pub fn list<'a>() -> Command<'a, Config> {
+    Command::new("list")
+        .description("Lists cached downloads, least recently used first")
+        .runner(runner(|_config: &Config, _matches, _exec| {
+            for entry in cache::entries()? {
+                println!(
+                    "{:>10} {:>4}d {}",
+                    HumanBytes(entry.size).to_string(),
+                    cache::days_unused(&entry),
+                    entry.url,
+                );
+            }
+
+            Ok(())
+        }))
+}
+
+pub fn prune<'a>() -> Command<'a, Config> {
+    Command::new("prune")
+        .description("Removes cached downloads unused for 30 days")
+        .options(|app| {
+            app.arg(
+                Arg::with_name("all")
+                    .long("all")
+                    .help("Removes all cached downloads"),
+            )
+        })
+        .runner(runner(|_config: &Config, matches, _exec| {
+            let mut freed = 0;
+
+            for entry in cache::entries()? {
+                if matches.is_present("all") || cache::days_unused(&entry) > PRUNE_AFTER_DAYS {
+                    if util::is_dry_run() {
+                        println!("{} {}", "Would remove".cyan(), entry.url);
+                    } else {
+                        cache::remove(&entry)?;
+                        println!("{} {}", "Removed".yellow(), entry.url);
+                    }
+
+                    freed += entry.size;
+                }
+            }
+
+            if util::is_dry_run() {
+                println!("{} {}.", "Would free".cyan(), HumanBytes(freed));
+            } else {
+                println!("{} {}.", "Freed".green(), HumanBytes(freed));
+            }
+            Ok(())
+        }))
+}
+
+pub fn path<'a>() -> Command<'a, Config> {
+    Command::new("path")
+        .description("Prints the directory of cached downloads")
+        .runner(runner(|_config: &Config, _matches, _exec| {
+            println!("{}", cache::dir()?.display());
+            Ok(())
+        }))
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .add_cmd(list())
+        .add_cmd(prune())
+        .add_cmd(path())
+        .into_cmd(
+            file_stem!(),
+            "Manages the cache of downloads made by `install`",
+        )
+}
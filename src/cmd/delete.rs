@@ -2,15 +2,15 @@ use clap_nested::{file_stem, Command};
 use colored::Colorize;
 use dialoguer::Confirmation;
 
+use super::{fix, runner};
+use crate::config::Config;
 use crate::util;
 
-pub fn cmd<'a>() -> Command<'a, str> {
+pub fn cmd<'a>() -> Command<'a, Config> {
     Command::new(file_stem!())
         .description("Deletes the development Minikube machine")
-        .runner(|env, _matches| {
-            if env != "dev" {
-                panic!("Only supported in \"dev\" environment.");
-            }
+        .runner(runner(|config: &Config, _matches, exec| {
+            config.require_env("dev")?;
 
             if Confirmation::new()
                 .with_text(&format!(
@@ -18,10 +18,12 @@ pub fn cmd<'a>() -> Command<'a, str> {
                     "delete".red(),
                 ))
                 .default(false)
-                .interact()
-                .unwrap()
+                .interact()?
             {
-                util::Command::new("minikube", vec!["--profile=mav", "delete"]).run();
+                fix::undo(exec, &config.minikube.profile)?;
+                util::Command::minikube(&config.minikube.profile, vec!["delete"]).run(exec)?;
             }
-        })
+
+            Ok(())
+        }))
 }
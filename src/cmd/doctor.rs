@@ -0,0 +1,301 @@
+use std::fs;
+use std::net::ToSocketAddrs;
+
+use clap::ArgMatches;
+use clap_nested::{file_stem, Command};
+use colored::Colorize;
+
+use super::fix::{self, LinuxResolver};
+use super::runner;
+use crate::config::Config;
+use crate::driver::{self, Driver};
+use crate::error::{Error, Result};
+use crate::util::{self, Executor, Installed};
+
+#[derive(PartialEq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    status: Status,
+    name: String,
+    detail: String,
+    /// The command to run when the check does not pass.
+    fix: Option<&'static str>,
+}
+
+impl Check {
+    fn pass<N: Into<String>, D: Into<String>>(name: N, detail: D) -> Self {
+        Check {
+            status: Status::Pass,
+            name: name.into(),
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn<N: Into<String>, D: Into<String>>(name: N, detail: D) -> Self {
+        Check {
+            status: Status::Warn,
+            ..Check::pass(name, detail)
+        }
+    }
+
+    fn fail<N: Into<String>, D: Into<String>>(name: N, detail: D, fix: &'static str) -> Self {
+        Check {
+            status: Status::Fail,
+            fix: Some(fix),
+            ..Check::pass(name, detail)
+        }
+    }
+}
+
+pub fn cmd<'a>() -> Command<'a, Config> {
+    Command::new(file_stem!())
+        .description("Checks what `install`, `start` and `fix` set up for development")
+        .options(|app| app.arg(driver::arg()))
+        .runner(runner(|config: &Config, matches, exec| {
+            config.require_env("dev")?;
+
+            let checks = run_checks(config, matches, exec)?;
+
+            for check in &checks {
+                let status = match check.status {
+                    Status::Pass => "PASS".green(),
+                    Status::Warn => "WARN".yellow(),
+                    Status::Fail => "FAIL".red(),
+                };
+
+                print!("{}  {:<30} {}", status, check.name, check.detail);
+
+                if let Some(fix) = check.fix {
+                    print!(" {} {}", "→ run".cyan(), fix);
+                }
+
+                println!();
+            }
+
+            let failures = checks
+                .iter()
+                .filter(|check| check.status == Status::Fail)
+                .count();
+
+            if failures == 0 {
+                Ok(())
+            } else {
+                Err(Error::ChecksFailed(failures))
+            }
+        }))
+}
+
+fn run_checks(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    exec: &dyn Executor,
+) -> Result<Vec<Check>> {
+    let mut checks = vec![];
+    let prefix = &config.install.prefix;
+
+    // Programs
+
+    for &cmd in util::MANAGED_PROGRAMS {
+        let required = config.versions.get(cmd).unwrap();
+
+        checks.push(
+            match util::installed_version(exec, util::locate(prefix, cmd), cmd, required) {
+                Installed::Current => Check::pass(cmd, required),
+
+                Installed::Outdated(found) => Check::fail(
+                    cmd,
+                    format!(
+                        "found {}, requires {}",
+                        found.as_ref().map_or("an unknown version", String::as_str),
+                        required,
+                    ),
+                    "mav upgrade",
+                ),
+
+                Installed::Missing => Check::fail(cmd, "not installed", "mav install"),
+            },
+        );
+    }
+
+    let driver = Driver::resolve(exec, config, matches)?;
+
+    for &program in driver.programs() {
+        if exec.command_exists(program) || prefix.join(program).is_file() {
+            checks.push(Check::pass(
+                program,
+                format!("used by the {} driver", driver.name()),
+            ));
+        } else {
+            checks.push(Check::fail(program, "not installed", "mav install"));
+        }
+    }
+
+    // Minikube
+
+    let status = match util::get_minikube_status(exec, &config.minikube.profile) {
+        Ok(status) => status,
+        Err(err) => {
+            checks.push(Check::fail(
+                "minikube status",
+                err.to_string(),
+                "mav install",
+            ));
+            return Ok(checks);
+        }
+    };
+
+    if status.is_healthy() {
+        checks.push(Check::pass("minikube status", status.to_string()));
+    } else if status.is_running() {
+        checks.push(Check::fail(
+            "minikube status",
+            status.to_string(),
+            "mav start",
+        ));
+    } else {
+        let detail = if status.is_created() {
+            "Stopped"
+        } else {
+            "not created"
+        };
+
+        // The remaining checks need a running machine.
+        checks.push(Check::fail("minikube status", detail, "mav start"));
+        return Ok(checks);
+    }
+
+    let context = format!("--kube-context={}", config.kube_context());
+
+    // Helm 3 talks to the apiserver directly, so listing releases is what needs the cluster.
+    checks.push(if config.versions.uses_tiller() {
+        match util::Command::new("helm", vec!["version", &context]).read(exec) {
+            Ok(_) => Check::pass("helm", "client and Tiller reachable"),
+            Err(_) => Check::fail("helm", "cannot reach Tiller", "mav start"),
+        }
+    } else {
+        // The same listing `status` reports releases from.
+        match util::Command::new("helm", vec!["list", &context, "--all-namespaces"]).read(exec) {
+            Ok(_) => Check::pass("helm", "cluster reachable"),
+            Err(_) => Check::fail("helm", "cannot reach the cluster", "mav start"),
+        }
+    });
+
+    // Network
+
+    let ip = match util::get_minikube_ip(exec, &config.minikube.profile) {
+        Ok(ip) => ip,
+        Err(err) => {
+            // Routes and DNS are checked against the IP.
+            checks.push(Check::fail("minikube ip", err.to_string(), "mav start"));
+            return Ok(checks);
+        }
+    };
+
+    checks.push(check_resolver(exec, &ip)?);
+
+    for (_, destination) in fix::ROUTES {
+        checks.push(check_route(exec, destination, &ip).unwrap_or_else(|err| {
+            Check::fail(format!("route {}", destination), err.to_string(), "mav fix")
+        }));
+    }
+
+    checks.push(match (fix::KNOWN_SERVICE, 443).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => Check::pass(fix::KNOWN_SERVICE, addr.ip().to_string()),
+            None => Check::fail(fix::KNOWN_SERVICE, "does not resolve", "mav fix"),
+        },
+        Err(_) => Check::fail(fix::KNOWN_SERVICE, "does not resolve", "mav fix"),
+    });
+
+    Ok(checks)
+}
+
+fn check_resolver(exec: &dyn Executor, ip: &str) -> Result<Check> {
+    let matches_file = |path, expected| match fs::read_to_string(path) {
+        Ok(ref content) if content == expected => Check::pass(path, "matches `mav fix`"),
+        Ok(_) => Check::fail(path, "differs from what `mav fix` writes", "mav fix"),
+        Err(_) => Check::fail(path, "missing", "mav fix"),
+    };
+
+    match util::OS {
+        util::Os::MacOs => Ok(matches_file(fix::MACOS_RESOLVER_FILE, fix::MACOS_RESOLVER)),
+
+        util::Os::Linux => match fix::detect_linux_resolver(exec)? {
+            LinuxResolver::SystemdResolved => {
+                let link = match fix::get_link_to(exec, ip) {
+                    Ok(link) => link,
+                    Err(err) => {
+                        return Ok(Check::fail("resolvectl dns", err.to_string(), "mav fix"))
+                    }
+                };
+
+                let name = format!("resolvectl dns {}", link);
+                let servers =
+                    util::Command::new("resolvectl", vec!["dns", &link]).read_unchecked(exec)?;
+
+                if servers.contains(fix::CLUSTER_DNS) {
+                    Ok(Check::pass(name, fix::CLUSTER_DNS))
+                } else {
+                    Ok(Check::fail(name, "misses cluster DNS", "mav fix"))
+                }
+            }
+
+            LinuxResolver::NetworkManagerDnsmasq => {
+                Ok(matches_file(fix::DNSMASQ_CONFIG_FILE, fix::DNSMASQ_CONFIG))
+            }
+
+            LinuxResolver::Unknown => Ok(Check::warn(
+                "resolver",
+                "neither systemd-resolved nor NetworkManager with dnsmasq is in use",
+            )),
+        },
+
+        _ => Err(Error::UnsupportedOs),
+    }
+}
+
+fn check_route(exec: &dyn Executor, destination: &str, ip: &str) -> Result<Check> {
+    let name = format!("route {}", destination);
+
+    Ok(match fix::get_route_gateway(exec, destination)? {
+        Some(ref gateway) if gateway == ip => Check::pass(name, format!("via {}", ip)),
+        Some(gateway) => Check::fail(name, format!("via {}, not {}", gateway, ip), "mav fix"),
+        None => Check::fail(name, "missing", "mav fix"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::App;
+
+    use super::*;
+    use crate::util::ScriptedExecutor;
+
+    #[test]
+    fn reports_a_failing_minikube_ip_as_a_check() {
+        let config = Config::default();
+        let matches = App::new("doctor")
+            .arg(driver::arg())
+            .get_matches_from(vec!["doctor", "--vm-driver=kvm2"]);
+
+        let exec = ScriptedExecutor::new()
+            .answer(
+                "minikube --profile=mav status",
+                0,
+                r#"{"Host":"Running","Kubelet":"Running","APIServer":"Running","Kubeconfig":"Configured"}"#,
+            )
+            .answer("minikube --profile=mav ip", 1, "");
+
+        let checks = run_checks(&config, &matches, &exec).unwrap();
+        let last = checks.last().unwrap();
+
+        assert_eq!(last.name, "minikube ip");
+        assert!(last.status == Status::Fail);
+    }
+}
@@ -0,0 +1,212 @@
+use clap::{AppSettings, Arg, ArgMatches};
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+
+use super::{confirm, release_selectors, runner};
+use crate::config::{Config, Service};
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
+
+pub fn pod<'a>() -> Command<'a, Config> {
+    Command::new("pod")
+        .description("Tears down pods")
+        .options(|app| {
+            app.arg(
+                Arg::with_name("name")
+                    .required(true)
+                    .help("Sets the pod to delete"),
+            )
+        })
+        .runner(runner(|config: &Config, matches, exec| {
+            let name = matches.value_of("name").unwrap();
+
+            let pod = config.pods.get(name).ok_or_else(|| Error::NotFound {
+                kind: "pod template",
+                name: name.into(),
+            })?;
+
+            if confirm(config, "tear down", name)? {
+                delete_pod(exec, config, name, pod.namespace(config))?;
+            }
+
+            Ok(())
+        }))
+}
+
+pub fn service<'a>() -> Command<'a, Config> {
+    Command::new("service")
+        .description("Tears down services")
+        .options(|app| {
+            app.arg(
+                Arg::with_name("name")
+                    .required(true)
+                    .help("Sets the service to delete"),
+            )
+        })
+        .runner(runner(|config: &Config, matches, exec| {
+            let name = matches.value_of("name").unwrap();
+            let service = config.services.get(name).cloned().unwrap_or_default();
+
+            if confirm(config, "tear down", name)? {
+                delete_release(exec, config, name, &service)?;
+            }
+
+            Ok(())
+        }))
+}
+
+fn all(config: &Config, matches: &ArgMatches<'_>, exec: &dyn Executor) -> Result<()> {
+    if !matches.is_present("all") {
+        return Ok(());
+    }
+
+    let mut list = vec!["list", "--short", "--kube-context", config.kube_context()];
+
+    // Helm 3 lists the current namespace only, unlike Helm 2.
+    if !config.versions.uses_tiller() {
+        list.push("--all-namespaces");
+    }
+
+    let releases = util::Command::new("helm", list).read(exec)?;
+    let releases: Vec<_> = releases.lines().collect();
+
+    // Only what `up` could have deployed, leaving other releases in the cluster alone.
+    let services: Vec<_> = config
+        .service_names()?
+        .into_iter()
+        .filter(|name| releases.contains(&name.as_str()))
+        .collect();
+
+    if services.is_empty() && config.pods.is_empty() {
+        println!("{}", "Nothing to tear down.".green());
+        return Ok(());
+    }
+
+    for name in &services {
+        println!("{} {}", "Service:".yellow(), name);
+    }
+
+    for name in config.pods.keys() {
+        println!("{} {}", "Pod:".yellow(), name);
+    }
+
+    if !confirm(config, "tear down", "these services and pods")? {
+        return Ok(());
+    }
+
+    for name in &services {
+        let service = config.services.get(name).cloned().unwrap_or_default();
+        delete_release(exec, config, name, &service)?;
+    }
+
+    for (name, pod) in &config.pods {
+        delete_pod(exec, config, name, pod.namespace(config))?;
+    }
+
+    Ok(())
+}
+
+fn delete_release(
+    exec: &dyn Executor,
+    config: &Config,
+    name: &str,
+    service: &Service,
+) -> Result<()> {
+    let context = config.kube_context();
+    let namespace = service.namespace(config);
+
+    if config.versions.uses_tiller() {
+        util::Command::new(
+            "helm",
+            vec!["delete", "--purge", name, "--kube-context", context],
+        )
+        .run(exec)?;
+    } else {
+        // Helm 3 purges always, and finds releases in their namespace.
+        util::Command::new(
+            "helm",
+            vec![
+                "uninstall",
+                name,
+                "--kube-context",
+                context,
+                "--namespace",
+                namespace,
+            ],
+        )
+        .run(exec)?;
+    }
+
+    for selector in &release_selectors(name) {
+        let list_pods = || {
+            util::Command::new(
+                "kubectl",
+                vec![
+                    &format!("--context={}", context),
+                    &format!("--namespace={}", namespace),
+                    "get",
+                    "pods",
+                    selector,
+                    "--output=name",
+                ],
+            )
+            .read(exec)
+        };
+
+        if list_pods()?.is_empty() {
+            continue;
+        }
+
+        let waited = util::Command::new(
+            "kubectl",
+            vec![
+                &format!("--context={}", context),
+                &format!("--namespace={}", namespace),
+                "wait",
+                "--for=delete",
+                "pods",
+                selector,
+                "--timeout=5m",
+            ],
+        )
+        .run(exec);
+
+        // Pods gone before the wait starts leave it no matching resources to fail on.
+        if waited.is_err() && !list_pods()?.is_empty() {
+            return waited;
+        }
+    }
+
+    Ok(())
+}
+
+fn delete_pod(exec: &dyn Executor, config: &Config, name: &str, namespace: &str) -> Result<()> {
+    util::Command::new(
+        "kubectl",
+        vec![
+            &format!("--context={}", config.kube_context()),
+            &format!("--namespace={}", namespace),
+            "delete",
+            "pod",
+            name,
+            "--ignore-not-found",
+            "--wait",
+        ],
+    )
+    .run(exec)
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .options(|app| {
+            app.setting(AppSettings::ArgRequiredElseHelp).arg(
+                Arg::with_name("all")
+                    .long("all")
+                    .help("Tears down all services and pods of the project"),
+            )
+        })
+        .add_cmd(pod())
+        .add_cmd(service())
+        .no_cmd(runner(all))
+        .into_cmd(file_stem!(), "Tears down stuff brought up by `up`")
+}
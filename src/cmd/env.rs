@@ -0,0 +1,52 @@
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+
+use super::runner;
+use crate::config::Config;
+
+pub fn list<'a>() -> Command<'a, Config> {
+    Command::new("list")
+        .description("Lists the environments configured for --env")
+        .runner(runner(|config: &Config, _matches, _exec| {
+            for name in config.environments.keys() {
+                let marker = if name == &config.env { "*" } else { " " };
+                println!("{} {}", marker.green(), describe(&config.with_env(name)?));
+            }
+
+            Ok(())
+        }))
+}
+
+pub fn current<'a>() -> Command<'a, Config> {
+    Command::new("current")
+        .description("Shows the environment selected by --env")
+        .runner(runner(|config: &Config, _matches, _exec| {
+            println!("{}", describe(config));
+            Ok(())
+        }))
+}
+
+fn describe(config: &Config) -> String {
+    let environment = config.environment();
+
+    let mut description = format!(
+        "{} context={} namespace={} protection={}",
+        config.env.bold(),
+        config.kube_context(),
+        config.namespace(),
+        environment.protection,
+    );
+
+    if let Some(kubeconfig) = &environment.kubeconfig {
+        description.push_str(&format!(" kubeconfig={}", kubeconfig.display()));
+    }
+
+    description
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .add_cmd(list())
+        .add_cmd(current())
+        .into_cmd(file_stem!(), "Shows the environments mav can work with")
+}
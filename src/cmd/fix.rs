@@ -1,81 +1,844 @@
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use clap::Arg;
 use clap_nested::{file_stem, Command};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use super::runner;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
+
+pub const CLUSTER_DNS: &'static str = "10.96.0.10";
+const CLUSTER_DOMAIN_ROUTE: &'static str = "~svc.cluster.local";
+
+/// A service every cluster has, which resolves from the host once the fix is in place.
+pub const KNOWN_SERVICE: &'static str = "kubernetes.default.svc.cluster.local";
+
+pub const MACOS_RESOLVER_FILE: &'static str = "/etc/resolver/mav";
+pub const MACOS_RESOLVER: &'static str =
+    "nameserver 10.96.0.10\ndomain svc.cluster.local\noptions ndots:5\n";
+
+pub const DNSMASQ_CONFIG_FILE: &'static str = "/etc/NetworkManager/dnsmasq.d/mav.conf";
+pub const DNSMASQ_CONFIG: &'static str = "server=/svc.cluster.local/10.96.0.10\n";
+
+const NETWORKMANAGER_CONFIG_FILE: &'static str = "/etc/NetworkManager/NetworkManager.conf";
+const NETWORKMANAGER_CONFIG_DIR: &'static str = "/etc/NetworkManager/conf.d";
+
+/// Routes to the cluster, as the destination `netstat -nr` shows on macOS and `ip route` takes.
+pub const ROUTES: &[(&str, &str)] = &[("10.96/12", "10.96.0.0/12"), ("172.17", "172.17.0.0/16")];
+
+/// Where `fix` records what it changed, one file per Minikube profile.
+const STATE_DIR: &'static str = ".local/share/mav/fix";
+
+/// What `fix` changed on the host for a profile, so that `--undo` reverts only that.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct State {
+    /// Files written by `fix`.
+    written: Vec<WrittenFile>,
+    routes: Vec<Route>,
+    /// Links that systemd-resolved sends cluster queries through.
+    links: Vec<String>,
+    /// Members of `bridge100` whose host filter was turned off.
+    host_filters: Vec<HostFilter>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct WrittenFile {
+    path: String,
+    /// What the file held before `fix` first wrote it, if it existed.
+    previous: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Route {
+    destination: String,
+    gateway: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct HostFilter {
+    interface: String,
+    /// The address the member was filtered to, which turning the filter back on needs.
+    address: String,
+}
+
+impl State {
+    fn path(profile: &str) -> Result<PathBuf> {
+        dirs::home_dir()
+            .map(|home| home.join(STATE_DIR).join(format!("{}.toml", profile)))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "cannot find the home directory").into()
+            })
+    }
 
-use crate::util;
+    fn load(profile: &str) -> Result<Self> {
+        let path = State::path(profile)?;
 
-pub fn cmd<'a>() -> Command<'a, str> {
+        if !path.is_file() {
+            return Ok(State::default());
+        }
+
+        toml::from_str(&fs::read_to_string(&path)?)
+            .map_err(|err| Error::InvalidConfig(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Loads what `fix` recorded for every profile but this one.
+    fn load_others(profile: &str) -> Result<Vec<Self>> {
+        let dir = State::path(profile)?.parent().unwrap().to_owned();
+        let mut others = vec![];
+
+        if !dir.is_dir() {
+            return Ok(others);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+                continue;
+            }
+
+            match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(other) if other != profile => others.push(State::load(other)?),
+                _ => {}
+            }
+        }
+
+        Ok(others)
+    }
+
+    /// Saves the state, unless commands are only being printed.
+    fn save(&self, profile: &str) -> Result<()> {
+        if util::is_dry_run() {
+            return Ok(());
+        }
+
+        let path = State::path(profile)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, toml::to_string(self).unwrap())?;
+
+        Ok(())
+    }
+
+    /// Records a file about to be written, keeping what it held before the first write only.
+    fn add_file(&mut self, path: &str, previous: Option<String>) {
+        if !self.written.iter().any(|recorded| recorded.path == path) {
+            self.written.push(WrittenFile {
+                path: path.into(),
+                previous,
+            });
+        }
+    }
+
+    fn add_route(&mut self, destination: &str, gateway: &str) {
+        self.routes.retain(|route| route.destination != destination);
+        self.routes.push(Route {
+            destination: destination.into(),
+            gateway: gateway.into(),
+        });
+    }
+
+    fn add_link(&mut self, link: &str) {
+        if !self.links.iter().any(|recorded| recorded == link) {
+            self.links.push(link.into());
+        }
+    }
+
+    fn add_host_filter(&mut self, interface: &str, address: &str) {
+        self.host_filters
+            .retain(|filter| filter.interface != interface);
+        self.host_filters.push(HostFilter {
+            interface: interface.into(),
+            address: address.into(),
+        });
+    }
+
+    fn is_empty(&self) -> bool {
+        self.written.is_empty()
+            && self.routes.is_empty()
+            && self.links.is_empty()
+            && self.host_filters.is_empty()
+    }
+}
+
+pub fn cmd<'a>() -> Command<'a, Config> {
     Command::new(file_stem!())
         .description("Fixes local network for the development Minikube machine")
-        .runner(|env, _matches| {
-            if env != "dev" {
-                panic!("Only supported in \"dev\" environment.");
-            }
-
-            match util::OS {
-                util::Os::MacOs => {
-                    // Add a resolver
-
-                    util::Command::new("sudo", vec!["mkdir", "-p", "/etc/resolver"])
-                        .then("sudo", vec!["rm", "-f", "/etc/resolver/mav"])
-                        .then(
-                            "sudo",
-                            vec![
-                                "bash",
-                                "-c",
-                                &vec![
-                                    "cat <<EOF >/etc/resolver/mav",
-                                    "nameserver 10.96.0.10",
-                                    "domain svc.cluster.local",
-                                    "options ndots:5",
-                                    "EOF",
-                                ]
-                                .join("\n"),
-                            ],
-                        )
-                        .run();
-
-                    // Add routes
-
-                    if util::get_minikube_status() == util::MinikubeStatus::Running {
-                        let netstat = util::Command::new("netstat", vec!["-nr"]).read();
-                        let mut netstat = netstat.lines();
-
-                        if netstat
-                            .find(|&line| line.starts_with("10.96/12 "))
-                            .is_some()
-                        {
-                            util::Command::new("sudo", vec!["route", "-n", "delete", "10.96/12"])
-                                .run();
-                        }
-
-                        if netstat.find(|&line| line.starts_with("172.17 ")).is_some() {
-                            util::Command::new("sudo", vec!["route", "-n", "delete", "172.17/16"])
-                                .run();
-                        }
-
-                        let ip = util::get_minikube_ip();
-
-                        util::Command::new("sudo", vec!["route", "-n", "add", "10.96.0.0/12", &ip])
-                            .then("sudo", vec!["route", "-n", "add", "172.17.0.0/16", &ip])
-                            .run();
+        .options(|app| {
+            app.arg(
+                Arg::with_name("undo")
+                    .long("undo")
+                    .help("Reverts the changes made by previous runs"),
+            )
+        })
+        .runner(runner(|config: &Config, matches, exec| {
+            config.require_env("dev")?;
+
+            if matches.is_present("undo") {
+                undo(exec, &config.minikube.profile)
+            } else {
+                run(exec, &config.minikube.profile)
+            }
+        }))
+}
+
+/// A change to the host network that `fix` found to be needed.
+enum Change {
+    /// Writes a resolver configuration, reloading NetworkManager for its dnsmasq.
+    WriteFile {
+        path: &'static str,
+        content: &'static str,
+    },
+    SetRoute {
+        destination: &'static str,
+        gateway: String,
+        /// The gateway the route currently goes through, if any.
+        current: Option<String>,
+    },
+    /// Sends cluster queries through a link with systemd-resolved.
+    SetLinkDns { link: String },
+    RemoveHostFilter {
+        interface: String,
+        /// The address the member is filtered to.
+        address: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::WriteFile { path, .. } => write!(f, "write {}", path),
+
+            Change::SetRoute {
+                destination,
+                gateway,
+                current: Some(current),
+            } => write!(
+                f,
+                "route {} via {} instead of {}",
+                destination, gateway, current
+            ),
+
+            Change::SetRoute {
+                destination,
+                gateway,
+                ..
+            } => write!(f, "route {} via {}", destination, gateway),
+
+            Change::SetLinkDns { link } => {
+                write!(f, "resolve *.svc.cluster.local through {}", link)
+            }
+
+            Change::RemoveHostFilter { interface, .. } => {
+                write!(f, "allow any hosts through {} on bridge100", interface)
+            }
+        }
+    }
+}
+
+impl Change {
+    fn apply(&self, exec: &dyn Executor, state: &mut State) -> Result<()> {
+        match self {
+            Change::WriteFile { path, content } => {
+                let previous = match fs::read_to_string(path) {
+                    Ok(previous) => Some(previous),
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(err.into()),
+                };
+
+                let dir = Path::new(path).parent().unwrap().display().to_string();
+                let mut command = util::Command::new("sudo", vec!["mkdir", "-p", &dir])
+                    .then("sudo", write_file_script(path, content));
+
+                if *path == DNSMASQ_CONFIG_FILE {
+                    command = command.then("sudo", vec!["systemctl", "reload", "NetworkManager"]);
+                }
+
+                command.run(exec)?;
+                state.add_file(path, previous);
+            }
+
+            Change::SetRoute {
+                destination,
+                gateway,
+                current,
+            } => {
+                let command = match util::OS {
+                    util::Os::MacOs if current.is_some() => {
+                        util::Command::new("sudo", vec!["route", "-n", "delete", destination])
+                            .then("sudo", vec!["route", "-n", "add", destination, gateway])
+                    }
+
+                    util::Os::MacOs => {
+                        util::Command::new("sudo", vec!["route", "-n", "add", destination, gateway])
                     }
 
-                    // Allow any hosts via involved interfaces
+                    _ => util::Command::new(
+                        "sudo",
+                        vec!["ip", "route", "replace", destination, "via", gateway],
+                    ),
+                };
+
+                command.run(exec)?;
+                state.add_route(destination, gateway);
+            }
+
+            Change::SetLinkDns { link } => {
+                util::Command::new("sudo", vec!["resolvectl", "dns", link, CLUSTER_DNS])
+                    .then(
+                        "sudo",
+                        vec!["resolvectl", "domain", link, CLUSTER_DOMAIN_ROUTE],
+                    )
+                    .run(exec)?;
+
+                state.add_link(link);
+            }
+
+            Change::RemoveHostFilter { interface, address } => {
+                util::Command::new(
+                    "sudo",
+                    vec!["ifconfig", "bridge100", "-hostfilter", interface],
+                )
+                .run(exec)?;
+
+                state.add_host_filter(interface, address);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Brings the host network in line with the Minikube machine, changing only what differs.
+pub fn run(exec: &dyn Executor, profile: &str) -> Result<()> {
+    let mut state = State::load(profile)?;
+    let plan = plan(exec, profile)?;
+
+    // Resolver files serve every profile, so each profile relying on one records it, keeping
+    // `undo` for another profile from taking it away.
+    let others = State::load_others(profile)?;
+
+    for &path in &[MACOS_RESOLVER_FILE, DNSMASQ_CONFIG_FILE] {
+        let current = match fs::read_to_string(path) {
+            Ok(ref current) if current == written_content(path) => current.clone(),
+            _ => continue,
+        };
+
+        // What the file held before mav, as far as anyone knows.
+        let previous = others
+            .iter()
+            .flat_map(|other| &other.written)
+            .find(|file| file.path == path)
+            .map_or(Some(current), |file| file.previous.clone());
+
+        state.add_file(path, previous);
+    }
+
+    state.save(profile)?;
+
+    if plan.is_empty() {
+        println!("{}", "Network is already correct.".green());
+        return Ok(());
+    }
+
+    // Ask for the password once rather than for each change.
+    util::Command::new("sudo", vec!["-v"]).run(exec)?;
+
+    // Saving after each change keeps what was applied revertible when a later change fails.
+    for change in &plan {
+        println!("{} {}", "Fixing:".yellow(), change);
+
+        let applied = change.apply(exec, &mut state);
+        state.save(profile)?;
+        applied?;
+    }
+
+    Ok(())
+}
+
+/// Describes the changes `run` would make, without making them.
+pub fn pending(exec: &dyn Executor, profile: &str) -> Result<Vec<String>> {
+    let plan = plan(exec, profile)?;
+    Ok(plan.iter().map(ToString::to_string).collect())
+}
+
+/// Observes the host network and lists the changes it needs. The state only tells `undo` what
+/// to revert, so changes lost since an earlier run are planned again.
+fn plan(exec: &dyn Executor, profile: &str) -> Result<Vec<Change>> {
+    let mut plan = vec![];
+
+    // Routes only need the host, even when Kubernetes in it is unhealthy. Without it there is
+    // nothing to check the network against.
+    if !util::get_minikube_status(exec, profile)?.is_running() {
+        return Err(Error::NotFound {
+            kind: "running Minikube machine",
+            name: profile.into(),
+        });
+    }
+
+    let ip = util::get_minikube_ip(exec, profile)?;
+
+    match util::OS {
+        util::Os::MacOs => {
+            plan.extend(plan_file(MACOS_RESOLVER_FILE, MACOS_RESOLVER));
+
+            plan.extend(plan_routes(exec, &ip)?);
+
+            // Members of the bridge change as the machine restarts, so what the bridge reports
+            // now decides, whatever an earlier run recorded.
+            for member in bridge_members(exec)? {
+                if let Some(address) = member.filter {
+                    plan.push(Change::RemoveHostFilter {
+                        interface: member.interface,
+                        address,
+                    });
+                }
+            }
+        }
+
+        util::Os::Linux => {
+            plan.extend(plan_routes(exec, &ip)?);
+
+            match detect_linux_resolver(exec)? {
+                LinuxResolver::SystemdResolved => {
+                    let link = get_link_to(exec, &ip)?;
 
-                    let interfaces = util::Command::new("ifconfig", vec!["bridge100"])
-                        .pipe("grep", vec!["member"])
-                        .pipe("awk", vec!["{print $2}"])
-                        .read();
+                    let dns = util::Command::new("resolvectl", vec!["dns", &link])
+                        .read_unchecked(exec)?;
+                    let domain = util::Command::new("resolvectl", vec!["domain", &link])
+                        .read_unchecked(exec)?;
 
-                    for interface in interfaces.lines() {
-                        util::Command::new(
-                            "sudo",
-                            vec!["ifconfig", "bridge100", "-hostfilter", interface],
-                        )
-                        .run();
+                    if !dns.contains(CLUSTER_DNS) || !domain.contains(CLUSTER_DOMAIN_ROUTE) {
+                        plan.push(Change::SetLinkDns { link });
                     }
                 }
 
-                _ => panic!("OS not supported."),
+                LinuxResolver::NetworkManagerDnsmasq => {
+                    plan.extend(plan_file(DNSMASQ_CONFIG_FILE, DNSMASQ_CONFIG));
+                }
+
+                LinuxResolver::Unknown => {
+                    eprintln!(
+                        "{}",
+                        "Neither systemd-resolved nor NetworkManager with dnsmasq is in use, \
+                         skipping cluster DNS."
+                            .yellow(),
+                    );
+                }
+            }
+        }
+
+        _ => return Err(Error::UnsupportedOs),
+    }
+
+    Ok(plan)
+}
+
+fn plan_file(path: &'static str, content: &'static str) -> Option<Change> {
+    match fs::read_to_string(path) {
+        Ok(ref current) if current == content => None,
+        _ => Some(Change::WriteFile { path, content }),
+    }
+}
+
+fn plan_routes(exec: &dyn Executor, ip: &str) -> Result<Vec<Change>> {
+    let mut plan = vec![];
+
+    for (_, destination) in ROUTES {
+        let current = get_route_gateway(exec, destination)?;
+
+        let correct = match &current {
+            Some(current) => current == ip,
+            None => false,
+        };
+
+        if !correct {
+            plan.push(Change::SetRoute {
+                destination,
+                gateway: ip.into(),
+                current,
+            });
+        }
+    }
+
+    Ok(plan)
+}
+
+/// Reverts what previous runs of `fix` recorded for the profile, leaving anything changed since
+/// then alone.
+pub fn undo(exec: &dyn Executor, profile: &str) -> Result<()> {
+    let state = State::load(profile)?;
+
+    if state.is_empty() {
+        println!("{}", "Nothing to undo.".green());
+        return Ok(());
+    }
+
+    let others = State::load_others(profile)?;
+
+    for file in &state.written {
+        // Other profiles still rely on it, and keep what it held before.
+        let shared = others
+            .iter()
+            .flat_map(|other| &other.written)
+            .any(|other| other.path == file.path);
+
+        if shared {
+            continue;
+        }
+
+        // A file changed since `fix` wrote it belongs to someone else now.
+        let content = match fs::read_to_string(&file.path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        if content != written_content(&file.path) {
+            continue;
+        }
+
+        match &file.previous {
+            Some(previous) => {
+                util::Command::new("sudo", write_file_script(&file.path, previous)).run(exec)?
+            }
+            None => util::Command::new("sudo", vec!["rm", "-f", &file.path]).run(exec)?,
+        }
+
+        if file.path == DNSMASQ_CONFIG_FILE {
+            util::Command::new("sudo", vec!["systemctl", "reload", "NetworkManager"]).run(exec)?;
+        }
+    }
+
+    for route in &state.routes {
+        // A route through another gateway was replaced by someone else, like a VPN.
+        if get_route_gateway(exec, &route.destination)?.as_ref() != Some(&route.gateway) {
+            continue;
+        }
+
+        match util::OS {
+            util::Os::MacOs => {
+                util::Command::new("sudo", vec!["route", "-n", "delete", &route.destination])
+                    .run(exec)?
+            }
+
+            _ => util::Command::new(
+                "sudo",
+                vec![
+                    "ip",
+                    "route",
+                    "del",
+                    &route.destination,
+                    "via",
+                    &route.gateway,
+                ],
+            )
+            .run(exec)?,
+        }
+    }
+
+    for link in &state.links {
+        if Path::new("/sys/class/net").join(link).exists() {
+            util::Command::new("sudo", vec!["resolvectl", "revert", link]).run(exec)?;
+        }
+    }
+
+    let members = if state.host_filters.is_empty() {
+        vec![]
+    } else {
+        bridge_members(exec)?
+    };
+
+    // Members that left the bridge, or got their filter back, are left alone.
+    for filter in &state.host_filters {
+        let unfiltered = members
+            .iter()
+            .any(|member| member.interface == filter.interface && member.filter.is_none());
+
+        if unfiltered {
+            util::Command::new(
+                "sudo",
+                vec![
+                    "ifconfig",
+                    "bridge100",
+                    "hostfilter",
+                    &filter.interface,
+                    &filter.address,
+                ],
+            )
+            .run(exec)?;
+        }
+    }
+
+    State::default().save(profile)
+}
+
+/// What `fix` writes to one of its files.
+fn written_content(path: &str) -> &'static str {
+    match path {
+        MACOS_RESOLVER_FILE => MACOS_RESOLVER,
+        DNSMASQ_CONFIG_FILE => DNSMASQ_CONFIG,
+        _ => "",
+    }
+}
+
+/// Arguments for `sudo` that write `content` to `path` exactly as given.
+fn write_file_script(path: &str, content: &str) -> Vec<String> {
+    let quote = |text: &str| format!("'{}'", text.replace("'", "'\\''"));
+
+    vec![
+        "bash".into(),
+        "-c".into(),
+        format!("printf '%s' {} >{}", quote(content), quote(path)),
+    ]
+}
+
+/// A member of the bridge to the Minikube VM on macOS.
+#[derive(Debug, PartialEq)]
+struct BridgeMember {
+    interface: String,
+    /// The address its host filter lets through, if the filter is on.
+    filter: Option<String>,
+}
+
+/// Lists the members of the bridge to the Minikube VM on macOS, if the bridge exists.
+fn bridge_members(exec: &dyn Executor) -> Result<Vec<BridgeMember>> {
+    let output = util::Command::new("ifconfig", vec!["-v", "bridge100"]).read_unchecked(exec)?;
+    Ok(parse_bridge_members(&output))
+}
+
+/// Reads `ifconfig -v` output, where each `member:` line is followed by details of the member,
+/// like `hostfilter 1 hw: 46:e0:4f:4a:cb:2 ip: 192.168.64.3` when its host filter is on.
+fn parse_bridge_members(output: &str) -> Vec<BridgeMember> {
+    let mut members: Vec<BridgeMember> = vec![];
+
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("member:") => members.extend(words.next().map(|interface| BridgeMember {
+                interface: interface.into(),
+                filter: None,
+            })),
+
+            Some("hostfilter") if words.next() != Some("0") => {
+                if let Some(member) = members.last_mut() {
+                    member.filter = words
+                        .skip_while(|&word| word != "ip:")
+                        .nth(1)
+                        .map(ToOwned::to_owned);
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    members
+}
+
+/// Finds the gateway of a route in `ROUTES`, given its destination.
+pub fn get_route_gateway(exec: &dyn Executor, destination: &str) -> Result<Option<String>> {
+    match util::OS {
+        util::Os::MacOs => {
+            let shown = ROUTES
+                .iter()
+                .find(|(_, route)| *route == destination)
+                .map_or(destination, |(shown, _)| shown);
+
+            let netstat = util::Command::new("netstat", vec!["-nr"]).read(exec)?;
+
+            Ok(netstat
+                .lines()
+                .find(|line| line.split_whitespace().next() == Some(shown))
+                .and_then(|line| line.split_whitespace().nth(1))
+                .map(ToOwned::to_owned))
+        }
+
+        util::Os::Linux => {
+            let route = util::Command::new("ip", vec!["route", "show", destination]).read(exec)?;
+            let mut words = route.split_whitespace();
+
+            Ok(words
+                .find(|&word| word == "via")
+                .and_then(|_| words.next())
+                .map(ToOwned::to_owned))
+        }
+
+        _ => Err(Error::UnsupportedOs),
+    }
+}
+
+pub enum LinuxResolver {
+    SystemdResolved,
+    NetworkManagerDnsmasq,
+    Unknown,
+}
+
+pub fn detect_linux_resolver(exec: &dyn Executor) -> Result<LinuxResolver> {
+    if !exec.command_exists("systemctl") {
+        return Ok(LinuxResolver::Unknown);
+    }
+
+    let is_active = |service| -> Result<bool> {
+        let state =
+            util::Command::new("systemctl", vec!["is-active", service]).read_unchecked(exec)?;
+        Ok(state == "active")
+    };
+
+    if is_active("systemd-resolved")? && exec.command_exists("resolvectl") {
+        Ok(LinuxResolver::SystemdResolved)
+    } else if is_active("NetworkManager")? && get_networkmanager_dns()? == Some("dnsmasq".into()) {
+        Ok(LinuxResolver::NetworkManagerDnsmasq)
+    } else {
+        Ok(LinuxResolver::Unknown)
+    }
+}
+
+/// Reads the DNS plugin NetworkManager uses, from its main config file and then the files in
+/// `conf.d`, later ones overriding earlier ones like NetworkManager does.
+fn get_networkmanager_dns() -> Result<Option<String>> {
+    let mut paths = vec![PathBuf::from(NETWORKMANAGER_CONFIG_FILE)];
+    let dir = Path::new(NETWORKMANAGER_CONFIG_DIR);
+
+    if dir.is_dir() {
+        let mut files = vec![];
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) == Some("conf") {
+                files.push(path);
             }
+        }
+
+        files.sort();
+        paths.extend(files);
+    }
+
+    let mut dns = None;
+
+    for path in paths {
+        if let Ok(content) = fs::read_to_string(&path) {
+            dns = parse_networkmanager_dns(&content).or(dns);
+        }
+    }
+
+    Ok(dns)
+}
+
+/// Finds `dns=` in the `[main]` section of a NetworkManager config file.
+fn parse_networkmanager_dns(content: &str) -> Option<String> {
+    let mut in_main = false;
+    let mut dns = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_main = line == "[main]";
+        } else if in_main && !line.starts_with('#') && !line.starts_with(';') {
+            let mut parts = line.splitn(2, '=');
+
+            if parts.next().map(str::trim) == Some("dns") {
+                dns = parts.next().map(|value| value.trim().to_string());
+            }
+        }
+    }
+
+    dns
+}
+
+/// Finds the network interface through which the host reaches the given IP.
+pub fn get_link_to(exec: &dyn Executor, ip: &str) -> Result<String> {
+    let route = util::Command::new("ip", vec!["route", "get", ip]).read(exec)?;
+    let mut words = route.split_whitespace();
+
+    words
+        .find(|&word| word == "dev")
+        .and_then(|_| words.next())
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| Error::NotFound {
+            kind: "network link",
+            name: ip.into(),
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn plans_only_routes_that_differ() {
+        let exec = util::ScriptedExecutor::new()
+            .answer(
+                "minikube --profile=mav status",
+                0,
+                r#"{"Host":"Running","Kubelet":"Running","APIServer":"Running","Kubeconfig":"Configured"}"#,
+            )
+            .answer("minikube --profile=mav ip", 0, "192.168.39.2")
+            .answer(
+                "ip route show 10.96.0.0/12",
+                0,
+                "10.96.0.0/12 via 192.168.39.2 dev virbr1",
+            )
+            .answer("ip route show 172.17.0.0/16", 0, "");
+
+        let plan = plan(&exec, "mav").unwrap();
+        let plan: Vec<_> = plan.iter().map(ToString::to_string).collect();
+
+        assert_eq!(plan, vec!["route 172.17.0.0/16 via 192.168.39.2"]);
+    }
+
+    #[test]
+    fn refuses_to_plan_without_a_running_machine() {
+        let exec = util::ScriptedExecutor::new().answer(
+            "minikube --profile=mav status",
+            0,
+            r#"{"Host":"Stopped","Kubelet":"Stopped","APIServer":"Stopped","Kubeconfig":"Configured"}"#,
+        );
+
+        match plan(&exec, "mav") {
+            Err(Error::NotFound { name, .. }) => assert_eq!(name, "mav"),
+            other => panic!("expected no running machine, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reads_dns_plugin_from_main_section_only() {
+        let config = "[main]\nplugins=ifupdown,keyfile\n#dns=none\ndns = dnsmasq\n\n[ifupdown]\ndns=default\n";
+        assert_eq!(parse_networkmanager_dns(config), Some("dnsmasq".into()));
+
+        let config = "[connection]\n# dns=dnsmasq is set elsewhere\ndns=dnsmasq\n";
+        assert_eq!(parse_networkmanager_dns(config), None);
+    }
+
+    #[test]
+    fn reads_host_filters_of_bridge_members() {
+        let output = "bridge100: flags=8863<UP,BROADCAST,SMART,RUNNING,SIMPLEX,MULTICAST> mtu 1500
+\tinet 192.168.64.1 netmask 0xffffff00 broadcast 192.168.64.255
+\tmember: en5 flags=3<LEARNING,DISCOVER>
+\t        ifmaxaddr 0 port 11 priority 0 path cost 0
+\t        hostfilter 1 hw: 46:e0:4f:4a:cb:2 ip: 192.168.64.3
+\tmember: en6 flags=3<LEARNING,DISCOVER>
+\t        ifmaxaddr 0 port 12 priority 0 path cost 0
+\tmedia: autoselect
+";
+
+        assert_eq!(
+            parse_bridge_members(output),
+            vec![
+                BridgeMember {
+                    interface: "en5".into(),
+                    filter: Some("192.168.64.3".into()),
+                },
+                BridgeMember {
+                    interface: "en6".into(),
+                    filter: None,
+                },
+            ],
+        );
+    }
+}
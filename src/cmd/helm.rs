@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use clap::{Arg, ArgMatches};
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+
+use super::{confirm, runner};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::util::{self, Executor, Installed};
+
+const PLUGIN_URL: &'static str = "https://github.com/helm/helm-2to3";
+
+pub fn migrate<'a>() -> Command<'a, Config> {
+    Command::new("migrate")
+        .description("Converts Helm 2 releases in the cluster to Helm 3, keeping the machine")
+        .options(|app| {
+            app.arg(
+                Arg::with_name("cleanup")
+                    .long("cleanup")
+                    .help("Removes Helm 2 release data and Tiller once releases are converted"),
+            )
+        })
+        .runner(runner(run_migrate))
+}
+
+fn run_migrate(config: &Config, matches: &ArgMatches<'_>, exec: &dyn Executor) -> Result<()> {
+    config.require_env("dev")?;
+
+    if config.versions.uses_tiller() {
+        return Err(Error::InvalidConfig(format!(
+            "versions.helm is {}, set it to a Helm 3 version and run `mav upgrade` first.",
+            config.versions.helm,
+        )));
+    }
+
+    // The plugin runs on Helm 3, and reads what Tiller stored in the cluster by itself.
+    let helm = util::locate(&config.install.prefix, "helm");
+
+    match util::check_version(exec, &helm, "helm", &config.versions.helm) {
+        Installed::Current => {}
+        _ => {
+            return Err(Error::CommandNotFound(format!(
+                "helm {}",
+                config.versions.helm
+            )))
+        }
+    }
+
+    // The Helm checked above, not whichever comes first on PATH, which may still be Helm 2.
+    let helm = helm.as_os_str();
+    let plugins = util::Command::new(helm, vec!["plugin", "list"]).read(exec)?;
+
+    if !plugins.lines().any(|line| line.starts_with("2to3")) {
+        util::Command::new(helm, vec!["plugin", "install", PLUGIN_URL]).run(exec)?;
+    }
+
+    let context = format!("--kube-context={}", config.kube_context());
+    let releases = helm2_releases(config, exec)?;
+
+    if releases.is_empty() {
+        println!("{}", "No Helm 2 releases to migrate.".green());
+    } else {
+        println!("{}", "Helm 2 releases:".yellow());
+
+        for release in &releases {
+            println!("  {}", release);
+        }
+
+        if !confirm(config, "migrate", "these releases")? {
+            return Ok(());
+        }
+
+        // Helm 3 keeps repositories and plugins elsewhere.
+        if let Some(home) = dirs::home_dir() {
+            if home.join(".helm").is_dir() {
+                util::Command::new(helm, vec!["2to3", "move", "config"]).run(exec)?;
+            }
+        }
+
+        for release in &releases {
+            util::Command::new(helm, vec!["2to3", "convert", &context, release]).run(exec)?;
+        }
+    }
+
+    if matches.is_present("cleanup") {
+        util::Command::new(helm, vec!["2to3", "cleanup", &context]).run(exec)?;
+    } else if !releases.is_empty() {
+        println!(
+            "{}",
+            "Helm 2 data and Tiller remain. Run `mav helm migrate --cleanup` once releases work."
+                .yellow(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Names of the releases Tiller keeps, which it stores as config maps, one per revision.
+fn helm2_releases(config: &Config, exec: &dyn Executor) -> Result<BTreeSet<String>> {
+    let names = util::Command::new(
+        "kubectl",
+        vec![
+            &format!("--context={}", config.kube_context()),
+            "--namespace=kube-system",
+            "get",
+            "configmaps",
+            "--selector=OWNER=TILLER",
+            "--output=jsonpath={.items[*].metadata.labels.NAME}",
+        ],
+    )
+    .read(exec)?;
+
+    Ok(names.split_whitespace().map(String::from).collect())
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .add_cmd(migrate())
+        .into_cmd(file_stem!(), "Helps with Helm versions")
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::App;
+
+    use super::*;
+
+    #[test]
+    fn converts_each_helm2_release_once() {
+        let mut config = Config::default();
+        config.versions.helm = "3.0.2".into();
+
+        let matches = App::new("migrate").get_matches_from(vec!["migrate"]);
+
+        let exec = util::ScriptedExecutor::new()
+            .answer("helm version", 0, "version.BuildInfo{Version:\"v3.0.2\"}")
+            .answer("helm plugin list", 0, "NAME\tVERSION\n2to3\t0.2.1")
+            .answer(
+                "kubectl --context=mav --namespace=kube-system get configmaps",
+                0,
+                "api api redis",
+            );
+
+        run_migrate(&config, &matches, &exec).unwrap();
+
+        let converted: Vec<_> = exec
+            .calls
+            .borrow()
+            .iter()
+            .filter(|call| call.starts_with("helm 2to3 convert"))
+            .cloned()
+            .collect();
+
+        assert_eq!(
+            converted,
+            vec![
+                "helm 2to3 convert --kube-context=mav api",
+                "helm 2to3 convert --kube-context=mav redis",
+            ],
+        );
+    }
+
+    #[test]
+    fn migrates_with_the_helm_in_the_prefix() {
+        let prefix = std::env::temp_dir().join(format!("mav-helm-{}", std::process::id()));
+        std::fs::create_dir_all(&prefix).unwrap();
+        std::fs::write(prefix.join("helm"), "").unwrap();
+
+        let mut config = Config::default();
+        config.versions.helm = "3.0.2".into();
+        config.install.prefix = prefix.clone();
+
+        let helm = prefix.join("helm").display().to_string();
+        let matches = App::new("migrate").get_matches_from(vec!["migrate"]);
+
+        let exec = util::ScriptedExecutor::new()
+            .answer(
+                &format!("{} version", helm),
+                0,
+                "version.BuildInfo{Version:\"v3.0.2\"}",
+            )
+            .answer(&format!("{} plugin list", helm), 0, "2to3\t0.2.1")
+            .answer("kubectl", 0, "api");
+
+        let migrated = run_migrate(&config, &matches, &exec);
+        std::fs::remove_dir_all(&prefix).unwrap();
+        migrated.unwrap();
+
+        let helm_calls: Vec<_> = exec
+            .calls
+            .borrow()
+            .iter()
+            .filter(|call| !call.starts_with("kubectl"))
+            .cloned()
+            .collect();
+
+        assert!(helm_calls.iter().all(|call| call.starts_with(&helm)));
+        assert!(helm_calls.contains(&format!("{} 2to3 convert --kube-context=mav api", helm)));
+    }
+}
@@ -1,94 +1,128 @@
-use clap_nested::{file_stem, Command};
+use std::path::{Path, PathBuf};
 
-use crate::util;
+use clap::{App, Arg, ArgMatches};
+use clap_nested::{file_stem, Command};
+use colored::Colorize;
 
-const KUBECTL_VERSION: &'static str = "1.15.3";
-const MINIKUBE_VERSION: &'static str = "1.3.1";
-const HELM_VERSION: &'static str = "2.14.3";
-const HELMFILE_VERSION: &'static str = "0.82.0";
+use super::runner;
+use crate::config::Config;
+use crate::driver::{self, Driver};
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
 
-pub fn cmd<'a>() -> Command<'a, str> {
+pub fn cmd<'a>() -> Command<'a, Config> {
     Command::new(file_stem!())
         .description("Installs all prerequisites")
-        .runner(|env, _matches| {
-            let platform = match util::OS {
-                util::Os::MacOs => "darwin",
-                util::Os::Linux => "linux",
-                _ => panic!("OS not supported."),
-            };
-
-            let kubectl_url = format!(
-                "https://storage.googleapis.com/kubernetes-release/release/v{}/bin/{}/amd64/kubectl",
-                KUBECTL_VERSION,
-                platform,
-            );
-
-            let docker_machine_driver_hyperkit_url = format!(
-                "https://storage.googleapis.com/minikube/releases/v{}/docker-machine-driver-hyperkit",
-                MINIKUBE_VERSION,
-            );
-
-            let minikube_url = format!(
-                "https://storage.googleapis.com/minikube/releases/v{}/minikube-{}-amd64",
-                MINIKUBE_VERSION, platform,
-            );
-
-            let helm_url = format!(
-                "https://get.helm.sh/helm-v{}-{}-amd64.tar.gz",
-                HELM_VERSION, platform,
-            );
-
-            let helmfile_url = format!(
-                "https://github.com/roboll/helmfile/releases/download/v{}/helmfile_{}_amd64",
-                HELMFILE_VERSION, platform,
-            );
-
-            match env {
-                "dev" => {
-                    match util::OS {
-                        util::Os::MacOs => {
-                            util::install_brew_formula("hyperkit");
-                        }
-
-                        _ => panic!("OS not supported."),
-                    }
-
-                    let helm_location: &str = &format!("{}-amd64/helm", platform);
-
-                    util::install_by_downloading("kubectl", kubectl_url)
-                        .enqueue_with_postinstall(
-                            "docker-machine-driver-hyperkit",
-                            docker_machine_driver_hyperkit_url,
-                            util::Command::new(
-                                "sudo",
-                                vec![
-                                    "install",
-                                    "-o",
-                                    "root",
-                                    "-m",
-                                    "4755",
-                                    "docker-machine-driver-hyperkit",
-                                    "/usr/local/bin/",
-                                ],
-                            )
-                            .then("rm", vec!["-f", "docker-machine-driver-hyperkit"]),
-                        )
-                        .enqueue("minikube", minikube_url)
-                        .enqueue_with_postinstall(
-                            "helm",
-                            helm_url,
-                            util::Command::new("tar", vec!["-xzvf", "helm"])
-                                .then("mv", vec![helm_location, "/usr/local/bin/"])
-                                .then("rm", vec!["-rf", &format!("{}-amd64", platform)])
-                                .then("rm", vec!["-f", "helm"]),
-                        )
-                        .enqueue("helmfile", helmfile_url)
-                        .run();
-                }
-
-                _ => {
-                    util::install_by_downloading("kubectl", kubectl_url).run();
-                }
-            }
-        })
+        .options(options)
+        .runner(runner(|config: &Config, matches, exec| {
+            install(config, matches, exec, false)
+        }))
+}
+
+pub fn options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(driver::arg()).arg(
+        Arg::with_name("prefix")
+            .long("prefix")
+            .takes_value(true)
+            .value_name("DIR")
+            .help("Sets where to install programs, defaults to ~/.local/share/mav/bin"),
+    )
+}
+
+/// Installs the programs the environment needs. Upgrading replaces only outdated programs.
+pub fn install(
+    config: &Config,
+    matches: &ArgMatches<'_>,
+    exec: &dyn Executor,
+    upgrade: bool,
+) -> Result<()> {
+    let platform = match util::OS {
+        util::Os::MacOs => "darwin",
+        util::Os::Linux => "linux",
+        _ => return Err(Error::UnsupportedOs),
+    };
+
+    let kubectl_url = format!(
+        "https://storage.googleapis.com/kubernetes-release/release/v{}/bin/{}/amd64/kubectl",
+        config.versions.kubectl, platform,
+    );
+
+    let minikube_url = format!(
+        "https://storage.googleapis.com/minikube/releases/v{}/minikube-{}-amd64",
+        config.versions.minikube, platform,
+    );
+
+    let helm_url = format!(
+        "https://get.helm.sh/helm-v{}-{}-amd64.tar.gz",
+        config.versions.helm, platform,
+    );
+
+    let helmfile_url = format!(
+        "https://github.com/roboll/helmfile/releases/download/v{}/helmfile_{}_amd64",
+        config.versions.helmfile, platform,
+    );
+
+    let pins = &config.sha256_pins();
+    let published =
+        |url: &str| util::Checksum::pinned_or(pins, url, util::Checksum::sidecar_of(url));
+
+    let kubectl_checksum = published(&kubectl_url);
+    let minikube_checksum = published(&minikube_url);
+    let helm_checksum = published(&helm_url);
+    // Helmfile releases come without checksum files.
+    let helmfile_checksum =
+        util::Checksum::pinned_or(pins, &helmfile_url, util::Checksum::Unpublished);
+
+    let prefix = matches
+        .value_of("prefix")
+        .map_or_else(|| config.install.prefix.clone(), PathBuf::from);
+
+    let mut installer = util::DownloadingInstaller::new(exec, &prefix, &config.versions);
+
+    if upgrade {
+        installer = installer.upgrading();
+    }
+
+    let installer = installer.enqueue("kubectl", kubectl_url, kubectl_checksum);
+
+    match config.env.as_str() {
+        "dev" => {
+            let driver = Driver::resolve(exec, config, matches)?;
+
+            driver
+                .install_prerequisites(exec, config, installer)?
+                .enqueue("minikube", minikube_url, minikube_checksum)
+                // Helm 2 and 3 archives share this layout. Helm 2 ones also carry Tiller, which
+                // `helm init` runs in the cluster instead.
+                .enqueue_archive(
+                    "helm",
+                    helm_url,
+                    helm_checksum,
+                    format!("{}-amd64/helm", platform),
+                )
+                .enqueue("helmfile", helmfile_url, helmfile_checksum)
+                .run()?;
+        }
+
+        _ => installer.run()?,
+    }
+
+    if !util::is_on_path(&prefix) {
+        warn_not_on_path(&prefix);
+    }
+
+    Ok(())
+}
+
+fn warn_not_on_path(prefix: &Path) {
+    println!(
+        "{} {} {}",
+        "Programs are installed to".yellow(),
+        prefix.display(),
+        "which is not on PATH. Add it in your shell profile:".yellow(),
+    );
+
+    println!();
+    println!("    export PATH=\"{}:$PATH\"", prefix.display());
+    println!();
 }
@@ -1,14 +1,111 @@
-use clap::Arg;
+use std::cell::{OnceCell, RefCell};
+
+use clap::{Arg, ArgMatches};
 use clap_nested::Commander;
+use colored::Colorize;
+use dialoguer::Confirmation;
+
+use crate::config::{Config, Protection};
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
 
+mod addons;
+mod cache;
 mod delete;
+mod doctor;
+mod down;
+mod env;
 mod fix;
+mod helm;
 mod install;
+mod profile;
 mod start;
+mod status;
 mod stop;
 mod up;
+mod upgrade;
+
+thread_local! {
+    static OUTCOME: RefCell<Result<()>> = const { RefCell::new(Ok(())) };
+}
+
+/// The loaded config, and the one the global options pick from it once clap has read them.
+struct Scope<'c> {
+    loaded: &'c Config,
+    picked: OnceCell<Config>,
+}
+
+pub fn run(config: &Config) -> Result<()> {
+    commander().run(&Scope {
+        loaded: config,
+        picked: OnceCell::new(),
+    });
+
+    OUTCOME.with(|outcome| outcome.replace(Ok(())))
+}
+
+/// Adapts a fallible runner to `clap_nested`, keeping its error for `run` to return.
+///
+/// Runners get their commands run through the given executor, which is the real shell here.
+fn runner<F>(run: F) -> impl Fn(&Config, &ArgMatches<'_>)
+where
+    F: Fn(&Config, &ArgMatches<'_>, &dyn Executor) -> Result<()>,
+{
+    move |config, matches| {
+        // Global options that failed to apply left their error for `run` already.
+        if OUTCOME.with(|outcome| outcome.borrow().is_err()) {
+            return;
+        }
+
+        let result = run(config, matches, &util::Shell);
+        OUTCOME.with(|outcome| *outcome.borrow_mut() = result);
+    }
+}
+
+/// Selects the resources of a release by either label charts put on them: `release` in older
+/// charts, or `app.kubernetes.io/instance` in those `helm create` scaffolds since Helm 2.10.
+///
+/// The second skips what carries both, so nothing is listed twice.
+fn release_selectors(name: &str) -> [String; 2] {
+    [
+        format!("--selector=release={}", name),
+        format!("--selector=app.kubernetes.io/instance={},!release", name),
+    ]
+}
+
+/// Asks before an action changes what runs in the environment, as its protection demands.
+fn confirm(config: &Config, action: &str, target: &str) -> Result<bool> {
+    match config.environment().protection {
+        Protection::None => Ok(true),
 
-pub fn commander<'a>() -> Commander<'a, (), str> {
+        Protection::Confirm => Ok(Confirmation::new()
+            .with_text(&format!(
+                "Do you really want to {} {} in \"{}\" environment?",
+                action.red(),
+                target,
+                config.env,
+            ))
+            .default(false)
+            .interact()?),
+
+        Protection::Locked => Err(Error::ProtectedEnv(config.env.clone())),
+    }
+}
+
+/// Applies the global options to the loaded config.
+fn pick(config: &Config, matches: &ArgMatches<'_>) -> Result<Config> {
+    let config = match matches.value_of("environment") {
+        Some(env) => config.with_env(env)?,
+        None => config.clone(),
+    };
+
+    Ok(match matches.value_of("profile") {
+        Some(profile) => config.with_profile(profile),
+        None => config,
+    })
+}
+
+fn commander<'a, 'c>() -> Commander<'a, Scope<'c>, Config> {
     Commander::new()
         .options(|app| {
             app.arg(
@@ -20,12 +117,55 @@ pub fn commander<'a>() -> Commander<'a, (), str> {
                     .value_name("STRING")
                     .help("Sets an environment, defaults to \"dev\""),
             )
+            .arg(
+                Arg::with_name("profile")
+                    .short("p")
+                    .long("profile")
+                    .global(true)
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .help(
+                        "Sets the Minikube profile, defaults to the one `mav profile switch` kept",
+                    ),
+            )
+            .arg(
+                Arg::with_name("dry-run")
+                    .long("dry-run")
+                    .global(true)
+                    .help("Prints commands that change the system instead of running them"),
+            )
+        })
+        .args(|scope: &Scope<'c>, matches| {
+            util::set_dry_run(matches.is_present("dry-run"));
+
+            let config = match pick(scope.loaded, matches) {
+                Ok(config) => scope.picked.get_or_init(|| config),
+                Err(err) => {
+                    OUTCOME.with(|outcome| *outcome.borrow_mut() = Err(err));
+                    return scope.loaded;
+                }
+            };
+
+            // Every kubectl, helm and helmfile run then talks to the environment's cluster.
+            if let Some(kubeconfig) = &config.environment().kubeconfig {
+                std::env::set_var("KUBECONFIG", kubeconfig);
+            }
+
+            config
         })
-        .args(|_args, matches| matches.value_of("environment").unwrap_or("dev"))
+        .add_cmd(addons::cmd())
+        .add_cmd(cache::cmd())
         .add_cmd(delete::cmd())
+        .add_cmd(doctor::cmd())
+        .add_cmd(down::cmd())
+        .add_cmd(env::cmd())
         .add_cmd(fix::cmd())
+        .add_cmd(helm::cmd())
         .add_cmd(install::cmd())
+        .add_cmd(profile::cmd())
         .add_cmd(start::cmd())
+        .add_cmd(status::cmd())
         .add_cmd(stop::cmd())
         .add_cmd(up::cmd())
+        .add_cmd(upgrade::cmd())
 }
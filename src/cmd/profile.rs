@@ -0,0 +1,136 @@
+use clap::Arg;
+use clap_nested::{file_stem, Command, CommandLike, Commander};
+use colored::Colorize;
+
+use super::{runner, start};
+use crate::config::{self, Config};
+use crate::error::{Error, Result};
+use crate::util;
+
+pub fn list<'a>() -> Command<'a, Config> {
+    Command::new("list")
+        .description("Lists the Minikube profiles that have a machine")
+        .runner(runner(|config: &Config, _matches, exec| {
+            let current = &config.minikube.profile;
+            let mut profiles = util::get_minikube_profiles(exec)?;
+
+            if !profiles.contains(current) {
+                profiles.push(current.clone());
+                profiles.sort();
+            }
+
+            for profile in &profiles {
+                let marker = if profile == current { "*" } else { " " };
+
+                let description = match util::get_minikube_machine(exec, profile)? {
+                    Some(machine) => format!(
+                        "driver={} cpus={} memory={}mb disk-size={}mb",
+                        machine.driver, machine.cpus, machine.memory, machine.disk_size,
+                    ),
+                    None => "not created".into(),
+                };
+
+                println!("{} {} {}", marker.green(), profile.bold(), description);
+            }
+
+            Ok(())
+        }))
+}
+
+pub fn switch<'a>() -> Command<'a, Config> {
+    Command::new("switch")
+        .description("Keeps using a Minikube profile in later runs")
+        .options(|app| {
+            app.arg(
+                Arg::with_name("name")
+                    .required(true)
+                    .help("Sets the profile to switch to"),
+            )
+        })
+        .runner(runner(|_config: &Config, matches, _exec| {
+            switch_to(matches.value_of("name").unwrap())
+        }))
+}
+
+pub fn create<'a>() -> Command<'a, Config> {
+    Command::new("create")
+        .description("Starts a machine under a new Minikube profile and switches to it")
+        .options(|app| {
+            start::options(app)
+                .arg(
+                    Arg::with_name("name")
+                        .required(true)
+                        .help("Sets the profile to create"),
+                )
+                .arg(
+                    Arg::with_name("kubernetes-version")
+                        .long("kubernetes-version")
+                        .takes_value(true)
+                        .value_name("VERSION")
+                        .help("Sets the Kubernetes version of the machine, like 1.15.2"),
+                )
+        })
+        .runner(runner(|config: &Config, matches, exec| {
+            config.require_env("dev")?;
+
+            let name = matches.value_of("name").unwrap();
+
+            if util::get_minikube_profiles(exec)?
+                .iter()
+                .any(|profile| profile == name)
+            {
+                return Err(Error::AlreadyExists {
+                    kind: "Minikube profile",
+                    name: name.into(),
+                });
+            }
+
+            let mut config = config.with_profile(name);
+
+            if let Some(version) = matches.value_of("kubernetes-version") {
+                config.minikube.kubernetes_version = version.trim_start_matches('v').into();
+            }
+
+            start::run(&config, matches, exec)?;
+            switch_to(name)
+        }))
+}
+
+fn switch_to(profile: &str) -> Result<()> {
+    if util::is_dry_run() {
+        println!("{} {}", "Would switch to Minikube profile".cyan(), profile);
+        return Ok(());
+    }
+
+    let path = config::switch_profile(profile)?;
+    println!(
+        "Switched to Minikube profile \"{}\" in {}",
+        profile,
+        path.display()
+    );
+
+    // A project `mav.toml` overrides the user config.
+    let config = Config::load()?;
+
+    if config.minikube.profile != profile {
+        println!(
+            "{} \"{}\" {}",
+            "The project sets profile".yellow(),
+            config.minikube.profile,
+            "which takes precedence here.".yellow(),
+        );
+    }
+
+    Ok(())
+}
+
+pub fn cmd() -> impl CommandLike<Config> {
+    Commander::new()
+        .add_cmd(list())
+        .add_cmd(switch())
+        .add_cmd(create())
+        .into_cmd(
+            file_stem!(),
+            "Manages Minikube profiles, each an isolated cluster",
+        )
+}
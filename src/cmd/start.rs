@@ -1,56 +1,531 @@
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+use std::{fmt, thread};
+
+use clap::{App, Arg, ArgMatches};
 use clap_nested::{file_stem, Command};
+use colored::Colorize;
+use dialoguer::Confirmation;
+use indicatif::{ProgressBar, ProgressStyle};
+
+use super::{addons, fix, runner};
+use crate::config::Config;
+use crate::driver::{self, Driver};
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
+
+/// The kube-system deployments that `mav up` needs, besides Tiller with Helm 2.
+const READY_DEPLOYMENTS: &[&str] = &["coredns"];
+const TILLER_DEPLOYMENT: &'static str = "tiller-deploy";
+const READY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A part of the cluster that `start` waits for.
+enum Component {
+    Apiserver,
+    Deployment(&'static str),
+    ClusterDns,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Component::Apiserver => write!(f, "apiserver"),
+            Component::Deployment(name) => write!(f, "{}", name),
+            Component::ClusterDns => write!(f, "cluster DNS"),
+        }
+    }
+}
+
+impl Component {
+    fn is_ready(&self, config: &Config, exec: &dyn Executor) -> Result<bool> {
+        let context = format!("--context={}", config.kube_context());
+
+        match self {
+            Component::Apiserver => {
+                let health = util::Command::new("kubectl", vec![&context, "get", "--raw=/healthz"])
+                    .probe(exec)?;
+
+                Ok(match health {
+                    Some(health) => health.trim() == "ok",
+                    None => false,
+                })
+            }
 
-// use super::fix;
-use crate::util;
+            Component::Deployment(name) => {
+                let available = util::Command::new(
+                    "kubectl",
+                    vec![
+                        &context,
+                        "--namespace=kube-system",
+                        "get",
+                        "deployment",
+                        name,
+                        "--output=jsonpath={.status.conditions[?(@.type==\"Available\")].status}",
+                    ],
+                )
+                .probe(exec)?;
 
-const MINIKUBE_CPUS: u16 = 2;
-const MINIKUBE_DISK_SIZE: &'static str = "20000mb";
-const MINIKUBE_ISO_VERSION: &'static str = "1.3.0";
-const MINIKUBE_KUBERNETES_VERSION: &'static str = "1.15.2";
-const MINIKUBE_MEMORY: &'static str = "2000mb";
+                Ok(match available {
+                    Some(available) => available.trim() == "True",
+                    None => false,
+                })
+            }
+
+            Component::ClusterDns => Ok(match (fix::KNOWN_SERVICE, 443).to_socket_addrs() {
+                Ok(mut addrs) => addrs.next().is_some(),
+                Err(_) => false,
+            }),
+        }
+    }
+}
 
-pub fn cmd<'a>() -> Command<'a, str> {
+pub fn cmd<'a>() -> Command<'a, Config> {
     Command::new(file_stem!())
         .description("Starts a Minikube machine for development")
-        .runner(|env, _matches| {
-            if env != "dev" {
-                panic!("Only supported in \"dev\" environment.");
+        .options(options)
+        .runner(runner(run))
+}
+
+pub fn options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(driver::arg())
+        .arg(
+            Arg::with_name("no-fix")
+                .long("no-fix")
+                .help("Skips fixing the host network for the machine, see `mav fix`"),
+        )
+        .arg(
+            Arg::with_name("no-wait")
+                .long("no-wait")
+                .help("Returns without waiting for the cluster to become ready"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .validator(|timeout| match timeout.parse::<u64>() {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("must be a number of seconds".into()),
+                })
+                .help("Sets how long to wait for the cluster, defaults to minikube.ready_timeout"),
+        )
+}
+
+pub fn run(config: &Config, matches: &ArgMatches<'_>, exec: &dyn Executor) -> Result<()> {
+    config.require_env("dev")?;
+
+    let vm_driver = format!(
+        "--vm-driver={}",
+        Driver::resolve(exec, config, matches)?.name()
+    );
+
+    let profile = &config.minikube.profile;
+    let mut status = util::get_minikube_status(exec, profile)?;
+
+    // Minikube keeps the resources a machine was created with, whatever `start` passes.
+    if status.is_created() {
+        if let Some(machine) = util::get_minikube_machine(exec, profile)? {
+            if offer_resize(config, exec, &machine, status.is_running())? {
+                status = util::get_minikube_status(exec, profile)?;
+            }
+        }
+    }
+
+    let running = util::MinikubeState::Running;
+
+    if !status.is_created() {
+        util::Command::minikube(
+            profile,
+            vec![
+                "start",
+                &format!("--cpus={}", config.minikube.cpus),
+                &format!("--disk-size={}", config.minikube.disk_size),
+                &format!(
+                    "--iso-url=https://storage.googleapis.com/minikube/iso/minikube-v{}.iso",
+                    config.minikube.iso_version,
+                ),
+                &format!(
+                    "--kubernetes-version=v{}",
+                    config.minikube.kubernetes_version
+                ),
+                &format!("--memory={}", config.minikube.memory),
+                &vm_driver,
+            ],
+        )
+        .run(exec)?;
+    } else if !status.is_healthy() {
+        if status.is_running() && status.kubelet == running && status.apiserver == running {
+            // Only kubectl points somewhere else.
+            util::Command::minikube(profile, vec!["update-context"]).run(exec)?;
+        } else {
+            // Starting a running host restarts the components that stopped.
+            util::Command::minikube(profile, vec!["start", &vm_driver]).run(exec)?;
+        }
+    }
+
+    if !config.minikube.addons.is_empty() {
+        addons::sync(config, exec)?;
+    }
+
+    // With several profiles, the current kubectl context can belong to another machine.
+    if config.versions.uses_tiller() {
+        util::Command::new(
+            "helm",
+            vec!["init", &format!("--kube-context={}", config.kube_context())],
+        )
+        .run(exec)?;
+    }
+
+    // The machine IP can change between boots, leaving routes and DNS pointing elsewhere.
+    let fix_network = config.minikube.fix_network && !matches.is_present("no-fix");
+
+    if fix_network {
+        fix::run(exec, profile)?;
+    }
+
+    if matches.is_present("no-wait") || util::is_dry_run() {
+        return Ok(());
+    }
+
+    let timeout = match matches.value_of("timeout") {
+        Some(timeout) => timeout.parse().unwrap(),
+        None => config.minikube.ready_timeout,
+    };
+
+    let mut components = vec![Component::Apiserver];
+    components.extend(
+        READY_DEPLOYMENTS
+            .iter()
+            .map(|&name| Component::Deployment(name)),
+    );
+
+    if config.versions.uses_tiller() {
+        components.push(Component::Deployment(TILLER_DEPLOYMENT));
+    }
+
+    // Only the fix makes cluster names resolve on the host.
+    if fix_network {
+        components.push(Component::ClusterDns);
+    }
+
+    wait_until_ready(config, exec, &components, timeout)
+}
+
+/// How a created machine differs from the configured resources.
+struct Drift {
+    /// One line per resource, as the machine has it and as configured.
+    changes: Vec<String>,
+    /// Whether restarting with new CPUs and memory catches up, which takes a driver that can
+    /// resize and an unchanged disk size.
+    reconfigurable: bool,
+    cpus: u16,
+    /// In megabytes.
+    memory: u64,
+}
+
+/// Compares a created machine with the config. Sizes the config doesn't express readably
+/// count as matching, since Minikube would refuse them anyway.
+fn drift(config: &Config, machine: &util::MinikubeMachine) -> Drift {
+    let cpus = config.minikube.cpus;
+    let memory = util::parse_megabytes(&config.minikube.memory).unwrap_or(machine.memory);
+    let disk_size = util::parse_megabytes(&config.minikube.disk_size).unwrap_or(machine.disk_size);
+
+    let mut changes = vec![];
+
+    if machine.cpus != cpus {
+        changes.push(format!("cpus: {}, configured {}", machine.cpus, cpus));
+    }
+
+    if machine.memory != memory {
+        changes.push(format!(
+            "memory: {}mb, configured {}mb",
+            machine.memory, memory
+        ));
+    }
+
+    if machine.disk_size != disk_size {
+        changes.push(format!(
+            "disk size: {}mb, configured {}mb",
+            machine.disk_size, disk_size
+        ));
+    }
+
+    let reconfigurable = match Driver::from_name(&machine.driver) {
+        Some(driver) => driver.can_resize() && machine.disk_size == disk_size,
+        None => false,
+    };
+
+    Drift {
+        changes,
+        reconfigurable,
+        cpus,
+        memory,
+    }
+}
+
+/// Explains how the machine differs from the config and offers to bring it in line: recreating
+/// it for a new disk size, or restarting it with new CPUs and memory where the driver allows.
+///
+/// Returns whether the machine changed, which leaves it stopped or deleted.
+fn offer_resize(
+    config: &Config,
+    exec: &dyn Executor,
+    machine: &util::MinikubeMachine,
+    running: bool,
+) -> Result<bool> {
+    let profile = &config.minikube.profile;
+    let drift = drift(config, machine);
+
+    if drift.changes.is_empty() {
+        return Ok(false);
+    }
+
+    println!(
+        "{}",
+        "The Minikube machine differs from the configuration:".yellow()
+    );
+
+    for change in &drift.changes {
+        println!("  {}", change);
+    }
+
+    let question = if drift.reconfigurable {
+        "Do you want to restart the machine with the configured resources?".to_string()
+    } else {
+        format!(
+            "Do you want to {} and recreate the machine? Everything in it is lost.",
+            "delete".red(),
+        )
+    };
+
+    if !Confirmation::new()
+        .with_text(&question)
+        .default(false)
+        .interact()?
+    {
+        return Ok(false);
+    }
+
+    match Driver::from_name(&machine.driver) {
+        Some(driver) if drift.reconfigurable => {
+            if running {
+                util::Command::minikube(profile, vec!["stop"]).run(exec)?;
             }
 
-            match util::get_minikube_status() {
-                util::MinikubeStatus::Running => {}
-
-                util::MinikubeStatus::Stopped => {
-                    util::Command::new(
-                        "minikube",
-                        vec!["--profile=mav", "start", "--vm-driver=hyperkit"],
-                    )
-                    .run();
-                }
-
-                util::MinikubeStatus::Unknown => {
-                    util::Command::new(
-                        "minikube",
-                        vec![
-                            "--profile=mav",
-                            "start",
-                            &format!("--cpus={}", MINIKUBE_CPUS),
-                            &format!("--disk-size={}", MINIKUBE_DISK_SIZE),
-                            &format!(
-                                "--iso-url=https://storage.googleapis.com/minikube/iso/minikube-v{}.iso",
-                                MINIKUBE_ISO_VERSION,
-                            ),
-                            &format!("--kubernetes-version=v{}", MINIKUBE_KUBERNETES_VERSION),
-                            &format!("--memory={}", MINIKUBE_MEMORY),
-                            "--vm-driver=hyperkit",
-                        ],
-                    )
-                    .run();
-                }
+            driver.resize(exec, profile, drift.cpus, drift.memory)?;
+            util::set_minikube_resources(exec, profile, drift.cpus, drift.memory)?;
+        }
+
+        _ => {
+            fix::undo(exec, profile)?;
+            util::Command::minikube(profile, vec!["delete"]).run(exec)?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Polls each component in turn until it is ready, failing on the first that runs out of time.
+fn wait_until_ready(
+    config: &Config,
+    exec: &dyn Executor,
+    components: &[Component],
+    timeout: u64,
+) -> Result<()> {
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}"));
+    spinner.enable_steady_tick(100);
+
+    for component in components {
+        spinner.set_message(&format!("Waiting for {}..", component));
+
+        while !component.is_ready(config, exec)? {
+            if Instant::now() >= deadline {
+                spinner.finish_with_message(&format!("{} {} is not ready", "✘".red(), component));
+
+                return Err(Error::NotReady {
+                    component: component.to_string(),
+                    timeout,
+                });
             }
 
-            util::Command::new("helm", vec!["init"]).run();
+            thread::sleep(READY_POLL_INTERVAL);
+        }
+    }
+
+    spinner.finish_with_message(&format!("{} Cluster is ready", "✓".green()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::ScriptedExecutor;
+
+    /// The profile of a machine created with the default config.
+    const MACHINE: &str =
+        r#"{"MachineConfig":{"VMDriver":"kvm2","CPUs":2,"Memory":2000,"DiskSize":20000}}"#;
+
+    #[test]
+    fn restarts_a_stopped_machine_without_resource_flags() {
+        let config = Config::default();
+
+        let matches = options(App::new("start")).get_matches_from(vec![
+            "start",
+            "--vm-driver=kvm2",
+            "--no-fix",
+            "--no-wait",
+        ]);
+
+        let exec = ScriptedExecutor::new()
+            .answer(
+                "minikube --profile=mav status",
+                0,
+                "host: Stopped\nkubelet:\napiserver:\nkubectl:",
+            )
+            .machine("mav", MACHINE);
+
+        run(&config, &matches, &exec).unwrap();
+
+        assert_eq!(
+            *exec.calls.borrow(),
+            vec![
+                "minikube --profile=mav status --output=json",
+                "minikube --profile=mav status",
+                "minikube --profile=mav start --vm-driver=kvm2",
+                "helm init --kube-context=mav",
+            ],
+        );
+    }
+
+    #[test]
+    fn restarts_a_running_host_whose_apiserver_stopped() {
+        let config = Config::default();
+
+        let matches = options(App::new("start")).get_matches_from(vec![
+            "start",
+            "--vm-driver=kvm2",
+            "--no-fix",
+            "--no-wait",
+        ]);
+
+        let exec = ScriptedExecutor::new()
+            .answer(
+                "minikube --profile=mav status --output=json",
+                0,
+                r#"{"Host":"Running","Kubelet":"Running","APIServer":"Stopped","Kubeconfig":"Configured"}"#,
+            )
+            .machine("mav", MACHINE);
+
+        run(&config, &matches, &exec).unwrap();
+
+        assert_eq!(
+            *exec.calls.borrow(),
+            vec![
+                "minikube --profile=mav status --output=json",
+                "minikube --profile=mav start --vm-driver=kvm2",
+                "helm init --kube-context=mav",
+            ],
+        );
+    }
+
+    #[test]
+    fn names_the_component_that_never_became_ready() {
+        let config = Config::default();
+
+        let matches = options(App::new("start")).get_matches_from(vec![
+            "start",
+            "--vm-driver=kvm2",
+            "--no-fix",
+            "--timeout=0",
+        ]);
+
+        let exec = ScriptedExecutor::new()
+            .answer(
+                "minikube --profile=mav status --output=json",
+                0,
+                r#"{"Host":"Running","Kubelet":"Running","APIServer":"Running","Kubeconfig":"Configured"}"#,
+            )
+            .answer("kubectl --context=mav get --raw=/healthz", 0, "ok")
+            .answer(
+                "kubectl --context=mav --namespace=kube-system get deployment coredns",
+                0,
+                "True",
+            )
+            .answer(
+                "kubectl --context=mav --namespace=kube-system get deployment tiller-deploy",
+                1,
+                "",
+            )
+            .machine("mav", MACHINE);
+
+        match run(&config, &matches, &exec) {
+            Err(Error::NotReady { component, .. }) => assert_eq!(component, "tiller-deploy"),
+            other => panic!("expected tiller-deploy not to be ready, got {:?}", other),
+        }
+    }
+
+    /// The machine `MACHINE` describes.
+    fn machine(driver: &str) -> util::MinikubeMachine {
+        util::MinikubeMachine {
+            driver: driver.into(),
+            cpus: 2,
+            memory: 2000,
+            disk_size: 20000,
+        }
+    }
+
+    #[test]
+    fn finds_no_drift_in_a_machine_as_configured() {
+        assert!(drift(&Config::default(), &machine("kvm2"))
+            .changes
+            .is_empty());
+    }
+
+    #[test]
+    fn reconfigures_for_cpus_and_memory() {
+        let mut config = Config::default();
+        config.minikube.cpus = 4;
+        config.minikube.memory = "4gb".into();
+
+        let drift = drift(&config, &machine("kvm2"));
+
+        assert_eq!(
+            drift.changes,
+            vec!["cpus: 2, configured 4", "memory: 2000mb, configured 4096mb"],
+        );
+        assert!(drift.reconfigurable);
+        assert_eq!((drift.cpus, drift.memory), (4, 4096));
+    }
+
+    #[test]
+    fn recreates_for_disk_size() {
+        let mut config = Config::default();
+        config.minikube.cpus = 4;
+        config.minikube.disk_size = "40000mb".into();
+
+        let drift = drift(&config, &machine("kvm2"));
+
+        assert_eq!(
+            drift.changes,
+            vec![
+                "cpus: 2, configured 4",
+                "disk size: 20000mb, configured 40000mb"
+            ],
+        );
+        assert!(!drift.reconfigurable);
+    }
+
+    #[test]
+    fn recreates_where_the_driver_cannot_resize() {
+        let mut config = Config::default();
+        config.minikube.memory = "3000mb".into();
+
+        let drift = drift(&config, &machine("docker"));
 
-            // fix::run(env, matches);
-        })
+        assert_eq!(drift.changes, vec!["memory: 2000mb, configured 3000mb"]);
+        assert!(!drift.reconfigurable);
+    }
 }
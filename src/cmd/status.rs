@@ -0,0 +1,478 @@
+use std::fmt::Display;
+
+use clap::{Arg, ArgMatches};
+use clap_nested::{file_stem, Command};
+use colored::Colorize;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::{fix, runner};
+use crate::config::Config;
+use crate::driver::{self, Driver};
+use crate::error::Result;
+use crate::util::{self, Executor, Installed};
+
+#[derive(Serialize)]
+struct Report {
+    environment: String,
+    context: String,
+    /// Only in "dev" environment.
+    minikube: Option<Machine>,
+    programs: Vec<Program>,
+    /// Only in "dev" environment, while the machine runs.
+    network: Option<Network>,
+    helm_major: u32,
+    /// Whether Helm reaches the cluster, through Tiller with Helm 2.
+    helm_ready: bool,
+    releases: Vec<Release>,
+}
+
+#[derive(Serialize)]
+struct Machine {
+    host: String,
+    kubelet: String,
+    apiserver: String,
+    kubeconfig: String,
+    ip: Option<String>,
+    driver: Setting<String>,
+    cpus: Setting<u16>,
+    /// In megabytes.
+    memory: Setting<u64>,
+    /// In megabytes.
+    disk_size: Setting<u64>,
+}
+
+/// A machine setting as configured, and as Minikube created the machine if it has.
+#[derive(Serialize)]
+struct Setting<T> {
+    configured: T,
+    actual: Option<T>,
+}
+
+impl<T: PartialEq> Setting<T> {
+    fn drifted(&self) -> bool {
+        match &self.actual {
+            Some(actual) => *actual != self.configured,
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Program {
+    name: String,
+    required: String,
+    installed: bool,
+    found: Option<String>,
+    current: bool,
+}
+
+#[derive(Serialize)]
+struct Network {
+    fixed: bool,
+    /// What `mav fix` would change.
+    pending: Vec<String>,
+}
+
+/// A release as `helm list --output=json` describes it, capitalized by Helm 2.
+#[derive(Serialize, Deserialize)]
+struct Release {
+    #[serde(alias = "Name")]
+    name: String,
+    #[serde(alias = "Namespace")]
+    namespace: String,
+    #[serde(alias = "Chart")]
+    chart: String,
+    #[serde(alias = "Revision", deserialize_with = "revision")]
+    revision: u32,
+    #[serde(alias = "Status")]
+    status: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReleaseList {
+    Helm2 {
+        #[serde(rename = "Releases")]
+        releases: Vec<Release>,
+    },
+    Helm3(Vec<Release>),
+}
+
+/// Reads a revision, which Helm 3 quotes.
+fn revision<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u32, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(number) => number
+            .as_u64()
+            .map(|number| number as u32)
+            .ok_or_else(|| de::Error::custom("revision is not a whole number")),
+        serde_json::Value::String(number) => number.parse().map_err(de::Error::custom),
+        _ => Err(de::Error::custom(
+            "revision is neither a number nor a string",
+        )),
+    }
+}
+
+pub fn cmd<'a>() -> Command<'a, Config> {
+    Command::new(file_stem!())
+        .description("Shows the state of the environment")
+        .options(|app| {
+            app.arg(driver::arg()).arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("Prints the state as JSON for scripts"),
+            )
+        })
+        .runner(runner(|config: &Config, matches, exec| {
+            let report = gather(config, matches, exec)?;
+
+            if matches.is_present("json") {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                print_report(&report);
+            }
+
+            Ok(())
+        }))
+}
+
+fn gather(config: &Config, matches: &ArgMatches<'_>, exec: &dyn Executor) -> Result<Report> {
+    let prefix = &config.install.prefix;
+    let context = config.kube_context();
+
+    let programs = util::MANAGED_PROGRAMS
+        .iter()
+        .map(|&cmd| {
+            let required = config.versions.get(cmd).unwrap();
+
+            let (installed, found) =
+                match util::installed_version(exec, util::locate(prefix, cmd), cmd, required) {
+                    Installed::Current => (true, Some(required.to_string())),
+                    Installed::Outdated(found) => (true, found),
+                    Installed::Missing => (false, None),
+                };
+
+            Program {
+                name: cmd.into(),
+                required: required.into(),
+                installed,
+                current: match &found {
+                    Some(found) => found == required,
+                    None => false,
+                },
+                found,
+            }
+        })
+        .collect();
+
+    let (minikube, network) = if config.env == "dev" {
+        // Without Minikube there is no machine to ask about.
+        let status = if exec.command_exists("minikube") {
+            util::get_minikube_status(exec, &config.minikube.profile)?
+        } else {
+            util::MinikubeStatus::unknown()
+        };
+
+        let created = util::get_minikube_machine(exec, &config.minikube.profile)?;
+
+        let ip = if status.is_running() {
+            Some(util::get_minikube_ip(exec, &config.minikube.profile)?)
+        } else {
+            None
+        };
+
+        let network = if status.is_running() {
+            let pending = fix::pending(exec, &config.minikube.profile)?;
+
+            Some(Network {
+                fixed: pending.is_empty(),
+                pending,
+            })
+        } else {
+            None
+        };
+
+        let machine = Machine {
+            host: status.host.to_string(),
+            kubelet: status.kubelet.to_string(),
+            apiserver: status.apiserver.to_string(),
+            kubeconfig: status.kubeconfig.to_string(),
+            ip,
+            driver: Setting {
+                configured: Driver::resolve(exec, config, matches)?.name().into(),
+                actual: created.as_ref().map(|created| created.driver.clone()),
+            },
+            cpus: Setting {
+                configured: config.minikube.cpus,
+                actual: created.as_ref().map(|created| created.cpus),
+            },
+            memory: Setting {
+                configured: megabytes(&config.minikube.memory),
+                actual: created.as_ref().map(|created| created.memory),
+            },
+            disk_size: Setting {
+                configured: megabytes(&config.minikube.disk_size),
+                actual: created.as_ref().map(|created| created.disk_size),
+            },
+        };
+
+        (Some(machine), network)
+    } else {
+        (None, None)
+    };
+
+    let list = if config.versions.uses_tiller() {
+        let tiller = util::Command::new(
+            "helm",
+            vec!["version", &format!("--kube-context={}", context)],
+        )
+        .read(exec);
+
+        match tiller {
+            Ok(_) => util::Command::new(
+                "helm",
+                vec![
+                    "list",
+                    &format!("--kube-context={}", context),
+                    "--output=json",
+                ],
+            )
+            .read(exec)
+            .ok(),
+            Err(_) => None,
+        }
+    } else {
+        // Helm 3 lists the current namespace only, unlike Helm 2.
+        util::Command::new(
+            "helm",
+            vec![
+                "list",
+                &format!("--kube-context={}", context),
+                "--all-namespaces",
+                "--output=json",
+            ],
+        )
+        .read(exec)
+        .ok()
+    };
+
+    let helm_ready = list.is_some();
+
+    let managed = config.service_names()?;
+    let releases = match list {
+        Some(list) => managed_releases(&list, &managed),
+        None => vec![],
+    };
+
+    Ok(Report {
+        environment: config.env.clone(),
+        context: context.into(),
+        minikube,
+        programs,
+        network,
+        helm_major: config.versions.helm_major(),
+        helm_ready,
+        releases,
+    })
+}
+
+/// Reads what `helm list --output=json` prints, keeping only what `up` deploys rather than
+/// everything else in the cluster.
+fn managed_releases(list: &str, managed: &[String]) -> Vec<Release> {
+    // Helm 2 wraps releases in an object and prints nothing at all without any.
+    let releases = match serde_json::from_str::<ReleaseList>(list) {
+        Ok(ReleaseList::Helm2 { releases }) | Ok(ReleaseList::Helm3(releases)) => releases,
+        Err(_) => vec![],
+    };
+
+    releases
+        .into_iter()
+        .filter(|release| managed.contains(&release.name))
+        .collect()
+}
+
+/// Minikube refuses sizes it cannot read, so those never match a created machine.
+fn megabytes(size: &str) -> u64 {
+    util::parse_megabytes(size).unwrap_or_default()
+}
+
+fn print_report(report: &Report) {
+    println!("{} context={}", report.environment.bold(), report.context);
+
+    if let Some(machine) = &report.minikube {
+        println!();
+        println!("{}", "Minikube".bold());
+
+        if machine.driver.actual.is_none() && machine.host == "Unknown" {
+            print_row("host", "not created, run `mav start`".red());
+        } else {
+            for (component, state) in &[
+                ("host", &machine.host),
+                ("kubelet", &machine.kubelet),
+                ("apiserver", &machine.apiserver),
+            ] {
+                print_row(component, colorize_state(state, "Running"));
+            }
+
+            print_row(
+                "kubeconfig",
+                colorize_state(&machine.kubeconfig, "Configured"),
+            );
+        }
+
+        if let Some(ip) = &machine.ip {
+            print_row("ip", ip.normal());
+        }
+
+        print_setting("driver", &machine.driver, |driver| driver.clone());
+        print_setting("cpus", &machine.cpus, u16::to_string);
+        print_setting("memory", &machine.memory, |size| format!("{}mb", size));
+        print_setting("disk size", &machine.disk_size, |size| {
+            format!("{}mb", size)
+        });
+    }
+
+    println!();
+    println!("{}", "Programs".bold());
+
+    for program in &report.programs {
+        let detail = match (&program.found, program.current) {
+            (Some(found), true) => found.green(),
+            (Some(found), false) => format!("{}, requires {}", found, program.required).red(),
+            (None, _) if program.installed => {
+                format!("unknown version, requires {}", program.required).red()
+            }
+            (None, _) => format!("not installed, requires {}", program.required).red(),
+        };
+
+        print_row(&program.name, detail);
+    }
+
+    if let Some(network) = &report.network {
+        println!();
+        println!("{}", "Network".bold());
+
+        if network.fixed {
+            print_row("fix", "in place".green());
+        } else {
+            let count = match network.pending.len() {
+                1 => "1 change".to_string(),
+                count => format!("{} changes", count),
+            };
+
+            print_row("fix", format!("{} pending, run `mav fix`", count).red());
+
+            for change in &network.pending {
+                print_row("", change);
+            }
+        }
+    }
+
+    println!();
+    println!("{}", format!("Helm {}", report.helm_major).bold());
+
+    let reached = if report.helm_major < 3 {
+        "tiller"
+    } else {
+        "cluster"
+    };
+
+    if report.helm_ready {
+        print_row(reached, "ready".green());
+    } else {
+        print_row(reached, "not reachable".red());
+    }
+
+    for release in &report.releases {
+        print_row(
+            &release.name,
+            format!(
+                "{} {} revision {} in {}",
+                release.status, release.chart, release.revision, release.namespace,
+            )
+            .normal(),
+        );
+    }
+}
+
+fn print_row<D: Display>(name: &str, detail: D) {
+    println!("  {:<12} {}", name, detail);
+}
+
+fn print_setting<T, F>(name: &str, setting: &Setting<T>, show: F)
+where
+    T: PartialEq,
+    F: Fn(&T) -> String,
+{
+    match &setting.actual {
+        Some(actual) if setting.drifted() => print_row(
+            name,
+            format!("{}, configured {}", show(actual), show(&setting.configured)).yellow(),
+        ),
+        Some(actual) => print_row(name, show(actual)),
+        None => print_row(name, format!("{} when created", show(&setting.configured))),
+    }
+}
+
+fn colorize_state(state: &str, healthy: &str) -> colored::ColoredString {
+    if state == healthy {
+        state.green()
+    } else {
+        state.red()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn managed() -> Vec<String> {
+        vec!["api".into(), "redis".into()]
+    }
+
+    #[test]
+    fn reads_helm2_releases() {
+        let list = r#"{"Next":"","Releases":[{"Name":"api","Revision":3,"Updated":"Mon Jan 6 10:00:00 2020","Status":"DEPLOYED","Chart":"api-0.1.0","AppVersion":"1.0","Namespace":"default"}]}"#;
+
+        let releases = managed_releases(list, &managed());
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].name, "api");
+        assert_eq!(releases[0].namespace, "default");
+        assert_eq!(releases[0].chart, "api-0.1.0");
+        assert_eq!(releases[0].revision, 3);
+        assert_eq!(releases[0].status, "DEPLOYED");
+    }
+
+    #[test]
+    fn reads_helm3_releases() {
+        let list = r#"[{"name":"redis","namespace":"cache","revision":"12","updated":"2020-01-06 10:00:00.000000 +0000 UTC","status":"deployed","chart":"redis-10.3.1","app_version":"5.0.7"}]"#;
+
+        let releases = managed_releases(list, &managed());
+
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].name, "redis");
+        assert_eq!(releases[0].namespace, "cache");
+        assert_eq!(releases[0].revision, 12);
+        assert_eq!(releases[0].status, "deployed");
+    }
+
+    #[test]
+    fn keeps_only_managed_releases() {
+        let list = r#"[
+            {"name":"api","namespace":"default","revision":"1","status":"deployed","chart":"api-0.1.0"},
+            {"name":"ingress","namespace":"kube-system","revision":"4","status":"deployed","chart":"nginx-ingress-1.29.1"}
+        ]"#;
+
+        let names: Vec<_> = managed_releases(list, &managed())
+            .into_iter()
+            .map(|release| release.name)
+            .collect();
+
+        assert_eq!(names, vec!["api"]);
+    }
+
+    #[test]
+    fn reads_no_releases_from_silent_helm2() {
+        assert!(managed_releases("", &managed()).is_empty());
+    }
+}
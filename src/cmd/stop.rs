@@ -1,29 +1,26 @@
 use clap_nested::{file_stem, Command};
 use dialoguer::Confirmation;
 
+use super::runner;
+use crate::config::Config;
 use crate::util;
 
-pub fn cmd<'a>() -> Command<'a, str> {
+pub fn cmd<'a>() -> Command<'a, Config> {
     Command::new(file_stem!())
         .description("Stops the development Minikube machine temporarily")
-        .runner(|env, _matches| {
-            if env != "dev" {
-                panic!("Only supported in \"dev\" environment.");
-            }
-
-            match util::get_minikube_status() {
-                util::MinikubeStatus::Running => {
-                    if Confirmation::new()
-                        .with_text("Do you really want to stop the Minikube machine?")
-                        .default(false)
-                        .interact()
-                        .unwrap()
-                    {
-                        util::Command::new("minikube", vec!["--profile=mav", "stop"]).run();
-                    }
-                }
+        .runner(runner(|config: &Config, _matches, exec| {
+            config.require_env("dev")?;
 
-                util::MinikubeStatus::Stopped | util::MinikubeStatus::Unknown => {}
+            // The host can run while Kubernetes in it does not, which still needs stopping.
+            if util::get_minikube_status(exec, &config.minikube.profile)?.is_running()
+                && Confirmation::new()
+                    .with_text("Do you really want to stop the Minikube machine?")
+                    .default(false)
+                    .interact()?
+            {
+                util::Command::minikube(&config.minikube.profile, vec!["stop"]).run(exec)?;
             }
-        })
+
+            Ok(())
+        }))
 }
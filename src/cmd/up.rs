@@ -1,22 +1,174 @@
+use clap::Arg;
 use clap_nested::{file_stem, Command, CommandLike, Commander};
 
-pub fn pod<'a>() -> Command<'a, str> {
+use super::{confirm, release_selectors, runner};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
+
+pub fn pod<'a>() -> Command<'a, Config> {
     Command::new("pod")
         .description("Spins up pods")
-        .runner(|env, matches| {
-            println!("pod: {}, {:?}", env, matches);
+        .options(|app| {
+            app.arg(
+                Arg::with_name("name")
+                    .required(true)
+                    .help("Sets the pod template in mav.toml to run"),
+            )
         })
+        .runner(runner(|config: &Config, matches, exec| {
+            let name = matches.value_of("name").unwrap();
+
+            let pod = config.pods.get(name).ok_or_else(|| Error::NotFound {
+                kind: "pod template",
+                name: name.into(),
+            })?;
+
+            if !confirm(config, "spin up", name)? {
+                return Ok(());
+            }
+
+            let namespace = pod.namespace(config);
+
+            let mut args = vec![
+                format!("--context={}", config.kube_context()),
+                format!("--namespace={}", namespace),
+                "run".into(),
+                name.into(),
+                format!("--image={}", pod.image),
+                "--restart=Never".into(),
+                "--rm".into(),
+                "--attach".into(),
+            ];
+
+            for (key, value) in &pod.env {
+                args.push(format!("--env={}={}", key, value));
+            }
+
+            if !pod.command.is_empty() {
+                args.push("--".into());
+                args.extend(pod.command.iter().cloned());
+            }
+
+            util::Command::new("kubectl", args).run(exec)
+        }))
 }
 
-pub fn service<'a>() -> Command<'a, str> {
+pub fn service<'a>() -> Command<'a, Config> {
     Command::new("service")
         .description("Spins up services")
-        .runner(|env, matches| {
-            println!("service: {}, {:?}", env, matches);
+        .options(|app| {
+            app.arg(
+                Arg::with_name("name")
+                    .required(true)
+                    .help("Sets the service to install or upgrade"),
+            )
         })
+        .runner(runner(|config: &Config, matches, exec| {
+            let name = matches.value_of("name").unwrap();
+
+            if !confirm(config, "spin up", name)? {
+                return Ok(());
+            }
+
+            let service = config.services.get(name).cloned().unwrap_or_default();
+            let namespace = service.namespace(config);
+            let context = config.kube_context();
+
+            let helmfile = config.root.join("helmfile.yaml");
+            let local_chart = config.root.join("charts").join(name);
+
+            match &service.chart {
+                Some(chart) => {
+                    // A chart that is not a project directory is a reference like `stable/redis`.
+                    let chart = match config.root.join(chart) {
+                        path if path.is_dir() => path.display().to_string(),
+                        _ => chart.clone(),
+                    };
+
+                    upgrade_chart(exec, config, name, &chart, &service.values, namespace)?;
+                }
+
+                None if helmfile.is_file() => {
+                    util::Command::new(
+                        "helmfile",
+                        vec![
+                            format!("--file={}", helmfile.display()),
+                            format!("--kube-context={}", context),
+                            format!("--namespace={}", namespace),
+                            format!("--selector=name={}", name),
+                            "sync".into(),
+                            "--args=--wait".into(),
+                        ],
+                    )
+                    .run(exec)?;
+                }
+
+                None if local_chart.is_dir() => {
+                    let chart = local_chart.display().to_string();
+                    upgrade_chart(exec, config, name, &chart, &service.values, namespace)?;
+                }
+
+                None => {
+                    return Err(Error::NotFound {
+                        kind: "service",
+                        name: name.into(),
+                    })
+                }
+            }
+
+            for selector in &release_selectors(name) {
+                let services = util::Command::new(
+                    "kubectl",
+                    vec![
+                        &format!("--context={}", context),
+                        &format!("--namespace={}", namespace),
+                        "get",
+                        "services",
+                        selector,
+                        "--output=wide",
+                    ],
+                )
+                .probe(exec)?;
+
+                // Either label may match nothing, which kubectl only complains about on stderr.
+                match services {
+                    Some(ref services) if !services.trim().is_empty() => print!("{}", services),
+                    _ => {}
+                }
+            }
+
+            Ok(())
+        }))
+}
+
+/// Installs or upgrades a release from a chart, waiting until its resources are ready.
+fn upgrade_chart(
+    exec: &dyn Executor,
+    config: &Config,
+    name: &str,
+    chart: &str,
+    values: &[String],
+    namespace: &str,
+) -> Result<()> {
+    let mut args = vec![
+        "upgrade".into(),
+        "--install".into(),
+        name.into(),
+        chart.into(),
+        format!("--kube-context={}", config.kube_context()),
+        format!("--namespace={}", namespace),
+        "--wait".into(),
+    ];
+
+    for values in values {
+        args.push(format!("--values={}", config.root.join(values).display()));
+    }
+
+    util::Command::new("helm", args).run(exec)
 }
 
-pub fn cmd() -> impl CommandLike<str> {
+pub fn cmd() -> impl CommandLike<Config> {
     Commander::new()
         .add_cmd(pod())
         .add_cmd(service())
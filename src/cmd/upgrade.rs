@@ -0,0 +1,13 @@
+use clap_nested::{file_stem, Command};
+
+use super::{install, runner};
+use crate::config::Config;
+
+pub fn cmd<'a>() -> Command<'a, Config> {
+    Command::new(file_stem!())
+        .description("Reinstalls prerequisites installed at another version than required")
+        .options(install::options)
+        .runner(runner(|config: &Config, matches, exec| {
+            install::install(config, matches, exec, true)
+        }))
+}
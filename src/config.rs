@@ -0,0 +1,555 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, io};
+
+use serde::{Deserialize, Serialize};
+use toml::value::{Table, Value};
+
+use crate::driver::Driver;
+use crate::error::{Error, Result};
+
+const PROJECT_CONFIG_FILE: &'static str = "mav.toml";
+const USER_CONFIG_FILE: &'static str = ".config/mav/config.toml";
+const DEFAULT_ENV: &'static str = "dev";
+const DEFAULT_NAMESPACE: &'static str = "default";
+const HELMFILE: &'static str = "helmfile.yaml";
+const INSTALL_PREFIX: &'static str = ".local/share/mav/bin";
+const INSTALL_PREFIX_ENV_VAR: &'static str = "MAV_PREFIX";
+
+const KUBECTL_VERSION: &'static str = "1.15.3";
+const MINIKUBE_VERSION: &'static str = "1.3.1";
+const HELM_VERSION: &'static str = "2.14.3";
+const HELMFILE_VERSION: &'static str = "0.82.0";
+
+/// SHA-256 digests of the helmfile binaries at `HELMFILE_VERSION`, by file name, as helmfile
+/// publishes no checksum files. Update them along with the version.
+const HELMFILE_SHA256: &[(&str, &str)] = &[];
+
+const MINIKUBE_PROFILE: &'static str = "mav";
+const MINIKUBE_CPUS: u16 = 2;
+const MINIKUBE_DISK_SIZE: &'static str = "20000mb";
+const MINIKUBE_ISO_VERSION: &'static str = "1.3.0";
+const MINIKUBE_KUBERNETES_VERSION: &'static str = "1.15.2";
+const MINIKUBE_MEMORY: &'static str = "2000mb";
+const MINIKUBE_READY_TIMEOUT: u64 = 300;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The environment selected by `--env`, not read from config files.
+    #[serde(skip)]
+    pub env: String,
+    /// The directory holding the project `mav.toml`, or the current directory without one.
+    #[serde(skip)]
+    pub root: PathBuf,
+    pub versions: Versions,
+    pub install: Install,
+    pub minikube: Minikube,
+    pub environments: BTreeMap<String, Environment>,
+    pub services: BTreeMap<String, Service>,
+    pub pods: BTreeMap<String, Pod>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Versions {
+    pub kubectl: String,
+    pub minikube: String,
+    pub helm: String,
+    pub helmfile: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Install {
+    /// Where `mav install` puts downloaded programs, overridden by `MAV_PREFIX`.
+    pub prefix: PathBuf,
+    /// SHA-256 digests of downloads by file name, like `helmfile_linux_amd64`, for the versions
+    /// required. They take precedence over built-in digests and the checksum files projects
+    /// publish, and are the only way to verify a helmfile version mav has no digests for.
+    pub sha256: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Minikube {
+    /// The Minikube profile, which names the machine and its kubectl context.
+    pub profile: String,
+    /// Detected from the OS when not set.
+    pub driver: Option<Driver>,
+    pub cpus: u16,
+    pub memory: String,
+    pub disk_size: String,
+    pub iso_version: String,
+    pub kubernetes_version: String,
+    /// Whether `mav start` runs `mav fix` when the host network does not match the machine.
+    pub fix_network: bool,
+    /// How many seconds `mav start` waits for the cluster to become ready.
+    pub ready_timeout: u64,
+    /// Whether `mav start` enables or disables each addon. Addons not listed are left alone.
+    pub addons: BTreeMap<String, bool>,
+}
+
+/// A cluster that `--env` can select.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Environment {
+    /// The kubeconfig file holding the context, `~/.kube/config` when not set.
+    pub kubeconfig: Option<PathBuf>,
+    /// The kubectl context, named after the environment, or the Minikube profile in `dev`,
+    /// when not set.
+    pub context: Option<String>,
+    /// The namespace of services and pods that do not set their own.
+    pub namespace: Option<String>,
+    pub protection: Protection,
+}
+
+/// How careful mav is before changing what runs in an environment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protection {
+    /// Changes go ahead without asking.
+    None,
+    /// Changes need to be confirmed.
+    #[default]
+    Confirm,
+    /// Changes are refused.
+    Locked,
+}
+
+/// How `mav up service` deploys a service. Without an entry, the service is looked up as a
+/// release in the project `helmfile.yaml`, then as a chart in `charts/<name>`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Service {
+    /// A chart directory relative to the project root, or a chart reference like `stable/redis`.
+    pub chart: Option<String>,
+    /// Values files relative to the project root.
+    pub values: Vec<String>,
+    pub namespace: Option<String>,
+}
+
+/// A template for one-off pods run by `mav up pod`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pod {
+    pub image: String,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+impl fmt::Display for Protection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protection::None => write!(f, "none"),
+            Protection::Confirm => write!(f, "confirm"),
+            Protection::Locked => write!(f, "locked"),
+        }
+    }
+}
+
+impl Service {
+    pub fn namespace<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.namespace
+            .as_ref()
+            .map_or(config.namespace(), String::as_str)
+    }
+}
+
+impl Pod {
+    pub fn namespace<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.namespace
+            .as_ref()
+            .map_or(config.namespace(), String::as_str)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let dev = Environment {
+            protection: Protection::None,
+            ..Environment::default()
+        };
+
+        Config {
+            env: DEFAULT_ENV.into(),
+            root: PathBuf::new(),
+            versions: Versions::default(),
+            install: Install::default(),
+            minikube: Minikube::default(),
+            environments: vec![(DEFAULT_ENV.into(), dev)].into_iter().collect(),
+            services: BTreeMap::new(),
+            pods: BTreeMap::new(),
+        }
+    }
+}
+
+impl Versions {
+    /// The major version of the required Helm, 2 for versions it cannot read.
+    pub fn helm_major(&self) -> u32 {
+        self.helm
+            .split('.')
+            .next()
+            .and_then(|major| major.parse().ok())
+            .unwrap_or(2)
+    }
+
+    /// Whether Minikube has the `docker` driver, which arrived in 1.7. Versions it cannot read
+    /// count as older.
+    pub fn minikube_has_docker_driver(&self) -> bool {
+        let mut parts = self
+            .minikube
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or_default());
+
+        (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        ) >= (1, 7)
+    }
+
+    /// Whether Helm goes through Tiller in the cluster, which Helm 3 dropped.
+    pub fn uses_tiller(&self) -> bool {
+        self.helm_major() < 3
+    }
+
+    /// The version required of a managed program.
+    pub fn get(&self, cmd: &str) -> Option<&str> {
+        match cmd {
+            "kubectl" => Some(&self.kubectl),
+            "minikube" => Some(&self.minikube),
+            "helm" => Some(&self.helm),
+            "helmfile" => Some(&self.helmfile),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Versions {
+    fn default() -> Self {
+        Versions {
+            kubectl: KUBECTL_VERSION.into(),
+            minikube: MINIKUBE_VERSION.into(),
+            helm: HELM_VERSION.into(),
+            helmfile: HELMFILE_VERSION.into(),
+        }
+    }
+}
+
+impl Default for Install {
+    fn default() -> Self {
+        Install {
+            prefix: dirs::home_dir().unwrap_or_default().join(INSTALL_PREFIX),
+            sha256: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Minikube {
+    fn default() -> Self {
+        Minikube {
+            profile: MINIKUBE_PROFILE.into(),
+            driver: None,
+            cpus: MINIKUBE_CPUS,
+            memory: MINIKUBE_MEMORY.into(),
+            disk_size: MINIKUBE_DISK_SIZE.into(),
+            iso_version: MINIKUBE_ISO_VERSION.into(),
+            kubernetes_version: MINIKUBE_KUBERNETES_VERSION.into(),
+            fix_network: true,
+            ready_timeout: MINIKUBE_READY_TIMEOUT,
+            addons: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads built-in defaults, then the user config, then the nearest project `mav.toml`,
+    /// each layer overriding the keys it sets.
+    pub fn load() -> Result<Self> {
+        let mut merged = Value::try_from(Config::default()).unwrap();
+        let project_config_path = project_config_path();
+
+        for path in [user_config_path(), project_config_path.clone()]
+            .iter()
+            .flatten()
+        {
+            merge(&mut merged, read(path)?);
+        }
+
+        let mut config: Config = merged
+            .try_into()
+            .map_err(|err| Error::InvalidConfig(err.to_string()))?;
+
+        if let Some(prefix) = env::var_os(INSTALL_PREFIX_ENV_VAR) {
+            config.install.prefix = prefix.into();
+        }
+
+        config.root = match project_config_path {
+            Some(path) => path.parent().unwrap().to_owned(),
+            None => env::current_dir()?,
+        };
+
+        Ok(config)
+    }
+
+    /// Selects an environment, which has to be configured under `[environments]`.
+    pub fn with_env(&self, env: &str) -> Result<Self> {
+        if !self.environments.contains_key(env) {
+            return Err(Error::NotFound {
+                kind: "environment",
+                name: env.into(),
+            });
+        }
+
+        Ok(Config {
+            env: env.into(),
+            ..self.clone()
+        })
+    }
+
+    /// Selects a Minikube profile for this run only, see `switch_profile` to keep it.
+    pub fn with_profile(&self, profile: &str) -> Self {
+        let mut config = self.clone();
+        config.minikube.profile = profile.into();
+        config
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environments[&self.env]
+    }
+
+    /// Digests to verify downloads against by file name: the built-in ones while the versions
+    /// they belong to are required, overridden by `install.sha256`.
+    pub fn sha256_pins(&self) -> BTreeMap<String, String> {
+        let mut pins = BTreeMap::new();
+
+        if self.versions.helmfile == HELMFILE_VERSION {
+            pins.extend(
+                HELMFILE_SHA256
+                    .iter()
+                    .map(|&(file, digest)| (file.to_string(), digest.to_string())),
+            );
+        }
+
+        pins.extend(self.install.sha256.clone());
+        pins
+    }
+
+    /// The kubectl context of the environment. Minikube names the `dev` context after its profile.
+    pub fn kube_context(&self) -> &str {
+        match &self.environment().context {
+            Some(context) => context,
+            None if self.env == DEFAULT_ENV => &self.minikube.profile,
+            None => &self.env,
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        self.environment()
+            .namespace
+            .as_ref()
+            .map_or(DEFAULT_NAMESPACE, String::as_str)
+    }
+
+    pub fn require_env(&self, env: &str) -> Result<()> {
+        if self.env == env {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedEnv(self.env.clone()))
+        }
+    }
+
+    /// Names of the services `mav up service` can deploy: those configured, the releases in the
+    /// project `helmfile.yaml` and the charts in `charts/`.
+    pub fn service_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<_> = self.services.keys().cloned().collect();
+        let helmfile = self.root.join(HELMFILE);
+        let charts = self.root.join("charts");
+
+        if helmfile.is_file() {
+            names.extend(parse_helmfile_releases(&fs::read_to_string(helmfile)?));
+        }
+
+        if charts.is_dir() {
+            for entry in fs::read_dir(charts)? {
+                let entry = entry?;
+
+                if entry.file_type()?.is_dir() {
+                    names.push(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+
+        names.sort();
+        names.dedup();
+
+        Ok(names)
+    }
+}
+
+/// Keeps a Minikube profile for later runs by setting it in the user config.
+///
+/// The user config is rewritten from what it parses to, which drops its comments.
+pub fn switch_profile(profile: &str) -> Result<PathBuf> {
+    let path = dirs::home_dir()
+        .map(|home| home.join(USER_CONFIG_FILE))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "cannot find the home directory"))?;
+
+    let mut config = if path.is_file() {
+        read(&path)?
+    } else {
+        Value::Table(Table::new())
+    };
+
+    let mut minikube = Table::new();
+    minikube.insert("profile".into(), Value::String(profile.into()));
+
+    let mut overlay = Table::new();
+    overlay.insert("minikube".into(), Value::Table(minikube));
+
+    merge(&mut config, Value::Table(overlay));
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(&path, toml::to_string(&config).unwrap())?;
+
+    Ok(path)
+}
+
+fn user_config_path() -> Option<PathBuf> {
+    let path = dirs::home_dir()?.join(USER_CONFIG_FILE);
+
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn project_config_path() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+
+    cwd.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+fn read(path: &Path) -> Result<Value> {
+    let content = fs::read_to_string(path)?;
+
+    content
+        .parse()
+        .map_err(|err| Error::InvalidConfig(format!("{}: {}", path.display(), err)))
+}
+
+/// Reads release names from the top-level `releases:` list of a helmfile.
+///
+/// Helmfile renders the file as a Go template before reading it as YAML, so it is scanned line
+/// by line rather than parsed. Names that are themselves templates are skipped.
+fn parse_helmfile_releases(content: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut in_releases = false;
+    // Where the `-` of each release and the keys after it start.
+    let mut item_indent = None;
+    let mut key_indent = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("{{") {
+            continue;
+        }
+
+        if indent == 0 && !trimmed.starts_with('-') {
+            in_releases = trimmed.starts_with("releases:");
+            item_indent = None;
+            key_indent = None;
+            continue;
+        }
+
+        if !in_releases {
+            continue;
+        }
+
+        let key = if trimmed.starts_with('-') && *item_indent.get_or_insert(indent) == indent {
+            let key = trimmed[1..].trim_start();
+            key_indent = Some(line.len() - key.len());
+            key
+        } else if key_indent == Some(indent) {
+            trimmed
+        } else {
+            continue;
+        };
+
+        let mut parts = key.splitn(2, ':');
+
+        if let (Some("name"), Some(value)) = (parts.next(), parts.next()) {
+            let name = value
+                .split(" #")
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'');
+
+            if !name.is_empty() && !name.contains("{{") {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    names
+}
+
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_release_names_from_helmfile() {
+        let helmfile = r#"
+repositories:
+  - name: stable
+    url: https://kubernetes-charts.storage.googleapis.com
+
+releases:
+  - name: api # the backend
+    chart: ./charts/api
+    set:
+      - name: image.tag
+        value: latest
+  - chart: stable/redis
+    name: "redis"
+{{ if eq .Environment.Name "dev" }}
+  - name: mailhog
+    chart: stable/mailhog
+{{ end }}
+  - name: {{ .Values.worker }}
+    chart: ./charts/worker
+"#;
+
+        assert_eq!(
+            parse_helmfile_releases(helmfile),
+            vec!["api", "redis", "mailhog"]
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::{fs, io};
+
+use clap::{Arg, ArgMatches};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use which::which;
+
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::util::{self, Executor};
+
+/// A Minikube VM driver.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Driver {
+    Hyperkit,
+    Kvm2,
+    Docker,
+    /// Minikube's `none` driver, which runs Kubernetes directly on the host.
+    #[serde(rename = "none")]
+    Bare,
+}
+
+const DRIVER_NAMES: &[&str] = &["hyperkit", "kvm2", "docker", "none"];
+
+/// Where the setuid hyperkit driver is installed, a directory on `PATH` where Minikube finds it,
+/// unless something other than root can write to it, as Homebrew does.
+const SETUID_DRIVER_DIR: &'static str = "/usr/local/bin";
+/// Where the setuid hyperkit driver goes otherwise, a directory mav creates owned by root.
+const FALLBACK_SETUID_DRIVER_DIR: &'static str = "/opt/mav/bin";
+
+pub fn arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("vm-driver")
+        .long("vm-driver")
+        .takes_value(true)
+        .value_name("DRIVER")
+        .possible_values(DRIVER_NAMES)
+        .help("Sets the Minikube VM driver, detected from the OS by default")
+}
+
+impl Driver {
+    pub fn name(self) -> &'static str {
+        match self {
+            Driver::Hyperkit => "hyperkit",
+            Driver::Kvm2 => "kvm2",
+            Driver::Docker => "docker",
+            Driver::Bare => "none",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hyperkit" => Some(Driver::Hyperkit),
+            "kvm2" => Some(Driver::Kvm2),
+            "docker" => Some(Driver::Docker),
+            "none" => Some(Driver::Bare),
+            _ => None,
+        }
+    }
+
+    /// Picks the driver from `--vm-driver`, then the config, then the OS.
+    pub fn resolve(exec: &dyn Executor, config: &Config, matches: &ArgMatches<'_>) -> Result<Self> {
+        if let Some(driver) = matches.value_of("vm-driver").and_then(Driver::from_name) {
+            return Ok(driver);
+        }
+
+        let driver = match config.minikube.driver {
+            Some(driver) => driver,
+            None => Driver::detect(exec, config)?,
+        };
+
+        if driver == Driver::Docker && !config.versions.minikube_has_docker_driver() {
+            return Err(Error::InvalidConfig(format!(
+                "the docker driver needs Minikube 1.7 or later, versions.minikube is {}.",
+                config.versions.minikube,
+            )));
+        }
+
+        Ok(driver)
+    }
+
+    fn detect(exec: &dyn Executor, config: &Config) -> Result<Self> {
+        match util::OS {
+            util::Os::MacOs => Ok(Driver::Hyperkit),
+
+            util::Os::Linux => {
+                if exec.command_exists("virsh") {
+                    Ok(Driver::Kvm2)
+                } else if config.versions.minikube_has_docker_driver() {
+                    Ok(Driver::Docker)
+                } else {
+                    Err(Error::InvalidConfig(format!(
+                        "Minikube {} needs libvirt for the kvm2 driver, which is not installed. \
+                         Install it, or set minikube.driver to \"none\" to run Kubernetes on \
+                         the host.",
+                        config.versions.minikube,
+                    )))
+                }
+            }
+
+            _ => Err(Error::UnsupportedOs),
+        }
+    }
+
+    /// The programs the driver needs on the host.
+    pub fn programs(self) -> &'static [&'static str] {
+        match self {
+            Driver::Hyperkit => &["hyperkit", "docker-machine-driver-hyperkit"],
+            Driver::Kvm2 => &["virsh", "docker-machine-driver-kvm2"],
+            Driver::Docker | Driver::Bare => &["docker"],
+        }
+    }
+
+    /// Whether `resize` can change CPUs and memory of an existing machine.
+    pub fn can_resize(self) -> bool {
+        match self {
+            Driver::Hyperkit | Driver::Kvm2 => true,
+            Driver::Docker | Driver::Bare => false,
+        }
+    }
+
+    /// Changes CPUs and memory of the stopped machine of a profile. Does nothing for drivers
+    /// that cannot, see `can_resize`.
+    pub fn resize(self, exec: &dyn Executor, profile: &str, cpus: u16, memory: u64) -> Result<()> {
+        match self {
+            // Minikube creates its domains in the system instance of libvirt.
+            Driver::Kvm2 => {
+                let cpus = cpus.to_string();
+                let memory = format!("{}M", memory);
+                let connect = "--connect=qemu:///system";
+
+                util::Command::new(
+                    "virsh",
+                    vec![connect, "setvcpus", profile, &cpus, "--config", "--maximum"],
+                )
+                .then(
+                    "virsh",
+                    vec![connect, "setvcpus", profile, &cpus, "--config"],
+                )
+                .then(
+                    "virsh",
+                    vec![connect, "setmaxmem", profile, &memory, "--config"],
+                )
+                .then(
+                    "virsh",
+                    vec![connect, "setmem", profile, &memory, "--config"],
+                )
+                .run(exec)
+            }
+
+            // The driver reads CPUs and memory from the machine config on every start.
+            Driver::Hyperkit => {
+                let path = exec
+                    .minikube_dir()
+                    .join("machines")
+                    .join(profile)
+                    .join("config.json");
+
+                util::update_json_file(&path, |machine| {
+                    machine["Driver"]["CPU"] = cpus.into();
+                    machine["Driver"]["Memory"] = memory.into();
+                })
+            }
+
+            Driver::Docker | Driver::Bare => Ok(()),
+        }
+    }
+
+    /// Installs what the driver needs on the host, enqueueing any downloads to `installer`.
+    pub fn install_prerequisites<'a>(
+        self,
+        exec: &dyn Executor,
+        config: &Config,
+        installer: util::DownloadingInstaller<'a>,
+    ) -> Result<util::DownloadingInstaller<'a>> {
+        match (self, &util::OS) {
+            (Driver::Hyperkit, util::Os::MacOs) => {
+                util::install_brew_formula(exec, "hyperkit")?;
+
+                let url = format!(
+                    "https://storage.googleapis.com/minikube/releases/v{}/docker-machine-driver-hyperkit",
+                    config.versions.minikube,
+                );
+
+                // Earlier versions of mav installed it to the prefix, where it would also count
+                // as installed.
+                let stale = installer.prefix().join("docker-machine-driver-hyperkit");
+
+                if stale.is_file() {
+                    util::Command::new("rm", vec!["-f".as_ref(), stale.as_os_str()]).run(exec)?;
+                }
+
+                let mut installed = which("docker-machine-driver-hyperkit")
+                    .ok()
+                    .into_iter()
+                    .chain(Some(
+                        Path::new(FALLBACK_SETUID_DRIVER_DIR)
+                            .join("docker-machine-driver-hyperkit"),
+                    ));
+
+                if installed.any(|driver| is_setuid_root(&driver)) {
+                    return Ok(installer);
+                }
+
+                // The driver has to be setuid root, which only sudo can install. It goes where only
+                // root can replace it, unlike the prefix.
+                let dir = if is_root_only(Path::new(SETUID_DRIVER_DIR)) {
+                    SETUID_DRIVER_DIR
+                } else if is_root_only(Path::new(FALLBACK_SETUID_DRIVER_DIR)) {
+                    if !util::is_on_path(Path::new(FALLBACK_SETUID_DRIVER_DIR)) {
+                        println!(
+                            "{} {} {}",
+                            "The hyperkit driver is installed to".yellow(),
+                            FALLBACK_SETUID_DRIVER_DIR,
+                            "which is not on PATH. Add it in your shell profile for Minikube."
+                                .yellow(),
+                        );
+                    }
+
+                    FALLBACK_SETUID_DRIVER_DIR
+                } else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!(
+                            "{} and {} are writable by users other than root, so the setuid \
+                             hyperkit driver cannot be installed there safely. Install \
+                             docker-machine-driver-hyperkit into a root-owned directory on PATH.",
+                            SETUID_DRIVER_DIR, FALLBACK_SETUID_DRIVER_DIR,
+                        ),
+                    )
+                    .into());
+                };
+
+                let staged = installer.staged("docker-machine-driver-hyperkit");
+                let postinstall = util::Command::new(
+                    "sudo",
+                    vec!["install", "-d", "-o", "root", "-m", "755", dir],
+                )
+                .then(
+                    "sudo",
+                    vec![
+                        "install".as_ref(),
+                        "-o".as_ref(),
+                        "root".as_ref(),
+                        "-m".as_ref(),
+                        "4755".as_ref(),
+                        staged.as_os_str(),
+                        dir.as_ref(),
+                    ],
+                );
+
+                Ok(installer.enqueue_with_postinstall(
+                    "docker-machine-driver-hyperkit",
+                    url.clone(),
+                    util::Checksum::pinned_or(
+                        &config.sha256_pins(),
+                        &url,
+                        util::Checksum::sidecar_of(&url),
+                    ),
+                    postinstall,
+                ))
+            }
+
+            (Driver::Kvm2, util::Os::Linux) => {
+                require(exec, "virsh")?;
+
+                let url = format!(
+                    "https://storage.googleapis.com/minikube/releases/v{}/docker-machine-driver-kvm2",
+                    config.versions.minikube,
+                );
+
+                Ok(installer.enqueue(
+                    "docker-machine-driver-kvm2",
+                    url.clone(),
+                    util::Checksum::pinned_or(
+                        &config.sha256_pins(),
+                        &url,
+                        util::Checksum::sidecar_of(&url),
+                    ),
+                ))
+            }
+
+            (Driver::Docker, util::Os::Linux) | (Driver::Bare, util::Os::Linux) => {
+                require(exec, "docker")?;
+                Ok(installer)
+            }
+
+            _ => Err(Error::UnsupportedOs),
+        }
+    }
+}
+
+/// Whether only root can change what `dir` holds, as a setuid program needs: its nearest existing
+/// ancestors included, since whoever can write to one can swap the directories below it.
+fn is_root_only(dir: &Path) -> bool {
+    dir.ancestors()
+        .filter_map(|dir| fs::metadata(dir).ok())
+        .all(|meta| meta.uid() == 0 && meta.mode() & 0o022 == 0)
+}
+
+/// Whether `program` runs as root, installed where only root can replace it.
+fn is_setuid_root(program: &Path) -> bool {
+    match (fs::metadata(program), program.parent()) {
+        (Ok(meta), Some(dir)) => meta.uid() == 0 && meta.mode() & 0o4000 != 0 && is_root_only(dir),
+        _ => false,
+    }
+}
+
+/// Checks for a prerequisite that mav cannot install by itself.
+fn require(exec: &dyn Executor, cmd: &str) -> Result<()> {
+    if util::check_install(exec, cmd) {
+        Ok(())
+    } else {
+        Err(Error::CommandNotFound(cmd.into()))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use clap::App;
+
+    use super::*;
+    use crate::util::ScriptedExecutor;
+
+    #[test]
+    fn picks_docker_only_where_minikube_has_it() {
+        let matches = App::new("start").arg(arg()).get_matches_from(vec!["start"]);
+        let exec = ScriptedExecutor::new().without("virsh");
+
+        let mut config = Config::default();
+        config.versions.minikube = "1.3.1".into();
+        assert!(Driver::resolve(&exec, &config, &matches).is_err());
+
+        config.versions.minikube = "1.7.0".into();
+        assert_eq!(
+            Driver::resolve(&exec, &config, &matches).unwrap(),
+            Driver::Docker
+        );
+
+        config.minikube.driver = Some(Driver::Docker);
+        config.versions.minikube = "1.6.2".into();
+        assert!(Driver::resolve(&exec, &config, &matches).is_err());
+    }
+}
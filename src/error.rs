@@ -0,0 +1,123 @@
+use std::{fmt, io, process};
+
+use colored::Colorize;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors that end a mav run.
+///
+/// Each category exits with its own code so that wrappers can tell them apart:
+///
+/// | Code | Category                                |
+/// |------|-----------------------------------------|
+/// | 2    | The OS is not supported                 |
+/// | 3    | The command is not supported in `--env` |
+/// | 4    | The configuration is invalid            |
+/// | 5    | A subprocess exited unsuccessfully      |
+/// | 6    | A download failed                       |
+/// | 7    | Any other IO error                      |
+/// | 8    | A named service, pod or env is missing  |
+/// | 9    | The environment is locked               |
+/// | 10   | Some `doctor` checks failed             |
+/// | 11   | A profile to create already exists      |
+/// | 12   | The cluster did not become ready        |
+/// | 127  | A required program is not installed     |
+#[derive(Debug)]
+pub enum Error {
+    UnsupportedOs,
+    UnsupportedEnv(String),
+    InvalidConfig(String),
+    CommandFailed { command: String, code: Option<i32> },
+    CommandNotFound(String),
+    DownloadFailed(Vec<(String, String)>),
+    Io(io::Error),
+    NotFound { kind: &'static str, name: String },
+    ProtectedEnv(String),
+    ChecksFailed(usize),
+    AlreadyExists { kind: &'static str, name: String },
+    NotReady { component: String, timeout: u64 },
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::UnsupportedOs => 2,
+            Error::UnsupportedEnv(_) => 3,
+            Error::InvalidConfig(_) => 4,
+            Error::CommandFailed { .. } => 5,
+            Error::DownloadFailed(_) => 6,
+            Error::Io(_) => 7,
+            Error::NotFound { .. } => 8,
+            Error::ProtectedEnv(_) => 9,
+            Error::ChecksFailed(_) => 10,
+            Error::AlreadyExists { .. } => 11,
+            Error::NotReady { .. } => 12,
+            Error::CommandNotFound(_) => 127,
+        }
+    }
+
+    pub fn exit(&self) -> ! {
+        eprintln!("{} {}", "error:".red().bold(), self);
+        process::exit(self.exit_code());
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnsupportedOs => write!(f, "OS not supported."),
+
+            Error::UnsupportedEnv(env) => {
+                write!(f, "Not supported in \"{}\" environment.", env)
+            }
+
+            Error::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+
+            Error::CommandFailed { command, code } => match code {
+                Some(code) => write!(f, "`{}` exited with code {}.", command, code),
+                None => write!(f, "`{}` was terminated by a signal.", command),
+            },
+
+            Error::CommandNotFound(program) => write!(f, "`{}` is not installed.", program),
+
+            Error::DownloadFailed(failures) => {
+                write!(f, "Failed to install ")?;
+
+                for (i, (cmd, reason)) in failures.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{} ({})", cmd, reason)?;
+                }
+
+                write!(f, ".")
+            }
+
+            Error::Io(err) => write!(f, "{}", err),
+
+            Error::NotFound { kind, name } => write!(f, "Cannot find {} \"{}\".", kind, name),
+
+            Error::ProtectedEnv(env) => write!(f, "\"{}\" environment is locked.", env),
+
+            Error::ChecksFailed(1) => write!(f, "1 check failed."),
+            Error::ChecksFailed(count) => write!(f, "{} checks failed.", count),
+
+            Error::AlreadyExists { kind, name } => {
+                write!(f, "{} \"{}\" already exists.", kind, name)
+            }
+
+            Error::NotReady { component, timeout } => write!(
+                f,
+                "{} did not become ready within {} seconds.",
+                component, timeout
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
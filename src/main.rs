@@ -1,8 +1,19 @@
 #![feature(async_closure)]
 
+mod cache;
 mod cmd;
+mod config;
+mod driver;
+mod error;
 mod util;
 
 fn main() {
-    cmd::commander().run(&());
+    if let Err(err) = run() {
+        err.exit();
+    }
+}
+
+fn run() -> error::Result<()> {
+    let config = config::Config::load()?;
+    cmd::run(&config)
 }
@@ -1,26 +1,54 @@
+#[cfg(test)]
+use std::cell::RefCell;
 use std::cmp;
-use std::ffi::{OsStr, OsString};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::os::unix::fs::PermissionsExt;
-use std::{fmt, fs};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fmt, fs, io, iter};
 
 use colored::Colorize;
 use duct::{Expression, ToExecutable};
 use futures::compat::{Compat, Future01CompatExt, Stream01CompatExt};
 use futures::{stream, Future, FutureExt, StreamExt};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::header::RANGE;
 use reqwest::r#async::Client;
-use tokio::fs::File;
+use reqwest::StatusCode;
+use tokio::fs::OpenOptions;
+use tokio::timer::Delay;
 use which::which;
 
-pub fn tokio_run<F: Future<Output = ()> + Send + 'static>(future: F) {
-    tokio::run(Compat::new(Box::pin(
-        future.map(|()| -> Result<(), ()> { Ok(()) }),
-    )));
+use crate::cache;
+use crate::config::Versions;
+use crate::error::{Error, Result};
+
+pub fn tokio_run<T, F>(future: F) -> T
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let output = Arc::new(Mutex::new(None));
+    let output_in_future = output.clone();
+
+    tokio::run(Compat::new(Box::pin(future.map(
+        move |value| -> std::result::Result<(), ()> {
+            *output_in_future.lock().unwrap() = Some(value);
+            Ok(())
+        },
+    ))));
+
+    let value = output.lock().unwrap().take().unwrap();
+    value
 }
 
 pub fn tokio_spawn<F: Future<Output = ()> + Send + 'static>(future: F) {
     tokio::spawn(Compat::new(Box::pin(
-        future.map(|()| -> Result<(), ()> { Ok(()) }),
+        future.map(|()| -> std::result::Result<(), ()> { Ok(()) }),
     )));
 }
 
@@ -42,13 +70,262 @@ pub const OS: Os = Os::Windows;
 #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
 pub const OS: Os = Os::Other;
 
-pub fn command_exists<C: AsRef<OsStr>>(cmd: C) -> bool {
-    which(cmd).is_ok()
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+const DOWNLOAD_BACKOFF: Duration = Duration::from_secs(1);
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Makes `Command::run` and `DownloadingInstaller::run` print what they would do instead.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::SeqCst);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::SeqCst)
+}
+
+/// What a pipeline left behind: its exit code and, when read, its stdout.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Output {
+    /// `None` when the last program was killed by a signal.
+    pub code: Option<i32>,
+    pub stdout: String,
+}
+
+impl Output {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Runs pipelines of programs on behalf of `Command`.
+///
+/// `Shell` runs them for real; tests use `ScriptedExecutor` to record them instead.
+pub trait Executor {
+    /// Runs a pipeline with its output going to the terminal.
+    fn run(&self, pipeline: &[Program]) -> Result<Output>;
+
+    /// Runs a pipeline capturing its stdout.
+    fn read(&self, pipeline: &[Program]) -> Result<Output>;
+
+    /// Runs a pipeline capturing its stdout and discarding its stderr, for polling.
+    fn probe(&self, pipeline: &[Program]) -> Result<Output>;
+
+    fn command_exists(&self, cmd: &str) -> bool;
+
+    /// Where Minikube keeps profiles and machines.
+    fn minikube_dir(&self) -> PathBuf;
+}
+
+/// Runs pipelines as child processes via `duct`.
+pub struct Shell;
+
+impl Executor for Shell {
+    fn run(&self, pipeline: &[Program]) -> Result<Output> {
+        let exp = pipeline_exp(pipeline)
+            .stdout_handle(os_pipe::dup_stdout()?)
+            .stderr_handle(os_pipe::dup_stderr()?);
+
+        let output = spawn(pipeline, exp)?;
+
+        Ok(Output {
+            code: output.status.code(),
+            stdout: String::new(),
+        })
+    }
+
+    fn read(&self, pipeline: &[Program]) -> Result<Output> {
+        let exp = pipeline_exp(pipeline)
+            .stdout_capture()
+            .stderr_handle(os_pipe::dup_stderr()?);
+
+        let output = spawn(pipeline, exp)?;
+
+        Ok(Output {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        })
+    }
+
+    fn probe(&self, pipeline: &[Program]) -> Result<Output> {
+        let exp = pipeline_exp(pipeline).stdout_capture().stderr_null();
+        let output = spawn(pipeline, exp)?;
+
+        Ok(Output {
+            code: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        })
+    }
+
+    fn command_exists(&self, cmd: &str) -> bool {
+        which(cmd).is_ok()
+    }
+
+    fn minikube_dir(&self) -> PathBuf {
+        minikube_dir()
+    }
+}
+
+/// Records the pipelines it is asked to run and answers them from a script.
+///
+/// Pipelines without a scripted answer succeed with empty output.
+///
+/// Minikube profiles come from a directory of its own, empty until `machine` adds one.
+#[cfg(test)]
+#[derive(Default)]
+pub struct ScriptedExecutor {
+    answers: Vec<(String, Output)>,
+    missing: Vec<String>,
+    minikube_dir: PathBuf,
+    pub calls: RefCell<Vec<String>>,
+}
+
+/// Counts scripted executors, so that each has a Minikube directory of its own.
+#[cfg(test)]
+static SCRIPTED_EXECUTORS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(test)]
+impl ScriptedExecutor {
+    pub fn new() -> Self {
+        let id = SCRIPTED_EXECUTORS.fetch_add(1, Ordering::SeqCst);
+
+        let mut exec = Self::default();
+        exec.minikube_dir = env::temp_dir().join(format!("mav-test-{}-{}", process::id(), id));
+        exec
+    }
+
+    /// Answers pipelines starting with `prefix` with the given exit code and stdout.
+    pub fn answer(mut self, prefix: &str, code: i32, stdout: &str) -> Self {
+        let output = Output {
+            code: Some(code),
+            stdout: stdout.into(),
+        };
+
+        self.answers.push((prefix.into(), output));
+        self
+    }
+
+    /// Makes `command_exists` report the command as missing.
+    pub fn without(mut self, cmd: &str) -> Self {
+        self.missing.push(cmd.into());
+        self
+    }
+
+    /// Gives Minikube a machine for the profile, created with the given profile config.
+    pub fn machine(self, profile: &str, config: &str) -> Self {
+        let dir = self.minikube_dir.join("profiles").join(profile);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config.json"), config).unwrap();
+        self
+    }
+
+    fn call(&self, pipeline: &[Program]) -> Result<Output> {
+        let rendered = render(pipeline);
+
+        let output = self
+            .answers
+            .iter()
+            .find(|(prefix, _)| rendered.starts_with(prefix.as_str()))
+            .map(|(_, output)| output.clone())
+            .unwrap_or(Output {
+                code: Some(0),
+                stdout: String::new(),
+            });
+
+        self.calls.borrow_mut().push(rendered);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+impl Executor for ScriptedExecutor {
+    fn run(&self, pipeline: &[Program]) -> Result<Output> {
+        self.call(pipeline)
+    }
+
+    fn read(&self, pipeline: &[Program]) -> Result<Output> {
+        self.call(pipeline)
+    }
+
+    fn probe(&self, pipeline: &[Program]) -> Result<Output> {
+        self.call(pipeline)
+    }
+
+    fn command_exists(&self, cmd: &str) -> bool {
+        !self.missing.iter().any(|missing| missing == cmd)
+    }
+
+    fn minikube_dir(&self) -> PathBuf {
+        self.minikube_dir.clone()
+    }
+}
+
+#[cfg(test)]
+impl Drop for ScriptedExecutor {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.minikube_dir);
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Command {
-    exp: Expression,
+    /// Pipelines run one after another, stopping at the first failure.
+    steps: Vec<Vec<Program>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    program: OsString,
+    args: Vec<OsString>,
+}
+
+impl Program {
+    fn new<P, A, I>(program: P, args: A) -> Self
+    where
+        P: ToExecutable,
+        A: IntoIterator<Item = I>,
+        I: Into<OsString>,
+    {
+        Program {
+            program: program.to_executable(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn exp(&self) -> Expression {
+        duct::cmd(self.program.as_os_str(), &self.args)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.program.to_string_lossy())?;
+
+        for arg in &self.args {
+            let arg = arg.to_string_lossy();
+
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                write!(f, " '{}'", arg.replace("'", "'\\''"))?;
+            } else {
+                write!(f, " {}", arg)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Program {
+    /// Renders the program like `Display`, with `sudo` highlighted.
+    fn highlighted(&self) -> String {
+        let rendered = self.to_string();
+
+        if self.program == "sudo" {
+            rendered.replacen("sudo", &"sudo".red().bold().to_string(), 1)
+        } else {
+            rendered
+        }
+    }
 }
 
 impl Command {
@@ -59,62 +336,155 @@ impl Command {
         I: Into<OsString>,
     {
         Command {
-            exp: duct::cmd(program, args),
+            steps: vec![vec![Program::new(program, args)]],
         }
     }
 
+    /// Runs `minikube` against a profile.
+    pub fn minikube<A, I>(profile: &str, args: A) -> Self
+    where
+        A: IntoIterator<Item = I>,
+        I: Into<OsString>,
+    {
+        let profile = OsString::from(format!("--profile={}", profile));
+        Command::new(
+            "minikube",
+            iter::once(profile).chain(args.into_iter().map(Into::into)),
+        )
+    }
+
     pub fn then<P, A, I>(mut self, program: P, args: A) -> Self
     where
         P: ToExecutable,
         A: IntoIterator<Item = I>,
         I: Into<OsString>,
     {
-        self.exp = self.exp.then(duct::cmd(program, args));
+        self.steps.push(vec![Program::new(program, args)]);
         self
     }
 
+    /// Pipes the stdout of the last program into another, in the same step.
+    #[allow(dead_code)]
     pub fn pipe<P, A, I>(mut self, program: P, args: A) -> Self
     where
         P: ToExecutable,
         A: IntoIterator<Item = I>,
         I: Into<OsString>,
     {
-        self.exp = self.exp.pipe(duct::cmd(program, args));
+        self.steps
+            .last_mut()
+            .unwrap()
+            .push(Program::new(program, args));
         self
     }
 
-    fn pipe_stdout(mut self) -> Self {
-        self.exp = self.exp.stdout_handle(os_pipe::dup_stdout().unwrap());
-        self
+    pub fn run(self, exec: &dyn Executor) -> Result<()> {
+        if is_dry_run() {
+            for step in &self.steps {
+                let rendered: Vec<_> = step.iter().map(Program::highlighted).collect();
+                println!("{} {}", "Would run:".cyan(), rendered.join(" | "));
+            }
+
+            return Ok(());
+        }
+
+        for step in &self.steps {
+            check_status(step, &exec.run(step)?)?;
+        }
+
+        Ok(())
     }
 
-    fn pipe_stderr(mut self) -> Self {
-        self.exp = self.exp.stderr_handle(os_pipe::dup_stderr().unwrap());
-        self
+    pub fn read(self, exec: &dyn Executor) -> Result<String> {
+        self.read_steps(exec, true)
     }
 
-    fn pipe_all(self) -> Self {
-        self.pipe_stdout().pipe_stderr()
+    /// Reads stdout without showing stderr, or nothing when a step fails.
+    pub fn probe(self, exec: &dyn Executor) -> Result<Option<String>> {
+        let mut stdout = String::new();
+
+        for step in &self.steps {
+            let output = exec.probe(step)?;
+
+            if !output.success() {
+                return Ok(None);
+            }
+
+            stdout.push_str(&output.stdout);
+        }
+
+        Ok(Some(stdout))
     }
 
-    pub fn run(self) {
-        let output = self.pipe_all().exp.unchecked().run().unwrap();
+    pub fn read_unchecked(self, exec: &dyn Executor) -> Result<String> {
+        self.read_steps(exec, false)
+    }
 
-        if !output.status.success() {
-            std::process::exit(output.status.code().unwrap_or(128));
+    /// Reads stdout whatever the steps exit with, without showing stderr, for trying flags that
+    /// older versions of a program reject.
+    pub fn probe_unchecked(self, exec: &dyn Executor) -> Result<String> {
+        let mut stdout = String::new();
+
+        for step in &self.steps {
+            stdout.push_str(&exec.probe(step)?.stdout);
         }
+
+        Ok(stdout.trim_end_matches(&['\n', '\r'][..]).to_owned())
     }
 
-    pub fn read(self) -> String {
-        self.pipe_stderr().exp.read().unwrap()
+    fn read_steps(self, exec: &dyn Executor, checked: bool) -> Result<String> {
+        let mut stdout = String::new();
+
+        for step in &self.steps {
+            let output = exec.read(step)?;
+
+            if checked {
+                check_status(step, &output)?;
+            }
+
+            stdout.push_str(&output.stdout);
+        }
+
+        Ok(stdout.trim_end_matches(&['\n', '\r'][..]).to_owned())
     }
+}
 
-    pub fn read_unchecked(self) -> String {
-        self.pipe_stderr().exp.unchecked().read().unwrap()
+fn render(pipeline: &[Program]) -> String {
+    pipeline
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn pipeline_exp(pipeline: &[Program]) -> Expression {
+    let mut programs = pipeline.iter();
+    let first = programs.next().unwrap().exp();
+    programs.fold(first, |exp, program| exp.pipe(program.exp()))
+}
+
+fn spawn(pipeline: &[Program], exp: Expression) -> Result<process::Output> {
+    exp.unchecked().run().map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            Error::CommandNotFound(pipeline[0].program.to_string_lossy().into_owned())
+        } else {
+            Error::Io(err)
+        }
+    })
+}
+
+fn check_status(step: &[Program], output: &Output) -> Result<()> {
+    if output.success() {
+        return Ok(());
     }
+
+    Err(Error::CommandFailed {
+        command: render(step),
+        code: output.code,
+    })
 }
 
-pub fn check_install<C: AsRef<OsStr> + fmt::Display>(cmd: C) -> bool {
+pub fn check_install(exec: &dyn Executor, cmd: &str) -> bool {
     print!(
         "{} {} {}",
         "Checking if".yellow(),
@@ -122,7 +492,7 @@ pub fn check_install<C: AsRef<OsStr> + fmt::Display>(cmd: C) -> bool {
         "is installed..".yellow()
     );
 
-    let installed = command_exists(cmd);
+    let installed = exec.command_exists(cmd);
 
     println!(
         " {}",
@@ -136,8 +506,107 @@ pub fn check_install<C: AsRef<OsStr> + fmt::Display>(cmd: C) -> bool {
     installed
 }
 
-pub fn install_brew() {
-    if !check_install("brew") {
+/// Whether a program mav manages is installed at the version it requires.
+#[derive(Debug, PartialEq)]
+pub enum Installed {
+    Missing,
+    /// Holds the version found, if the program reported one.
+    Outdated(Option<String>),
+    Current,
+}
+
+/// The programs whose versions the config pins.
+pub const MANAGED_PROGRAMS: &[&str] = &["kubectl", "minikube", "helm", "helmfile"];
+
+/// The arguments that make each managed program print its version.
+const VERSION_ARGS: &[(&str, &[&str])] = &[
+    ("kubectl", &["version", "--client", "--short"]),
+    ("minikube", &["version"]),
+    ("helm", &["version", "--client"]),
+    ("helmfile", &["--version"]),
+];
+
+/// Like `check_install`, but also compares the version the program reports with `required`.
+pub fn check_version<P: Into<OsString>>(
+    exec: &dyn Executor,
+    program: P,
+    cmd: &str,
+    required: &str,
+) -> Installed {
+    print!(
+        "{} {} {} {}",
+        "Checking if".yellow(),
+        cmd,
+        required,
+        "is installed..".yellow()
+    );
+
+    let installed = installed_version(exec, program, cmd, required);
+
+    match &installed {
+        Installed::Missing => println!(" {}", "✘".red()),
+        Installed::Outdated(Some(found)) => println!(" {} found {}", "✘".red(), found),
+        Installed::Outdated(None) => println!(" {} found an unknown version", "✘".red()),
+        Installed::Current => println!(" {}", "✓".green()),
+    }
+
+    installed
+}
+
+/// Compares the version a managed program reports with `required`, without printing.
+pub fn installed_version<P: Into<OsString>>(
+    exec: &dyn Executor,
+    program: P,
+    cmd: &str,
+    required: &str,
+) -> Installed {
+    let program = program.into();
+    let args = VERSION_ARGS
+        .iter()
+        .find(|(name, _)| *name == cmd)
+        .map_or(&[][..], |(_, args)| args);
+
+    if !exec.command_exists(&program.to_string_lossy()) {
+        Installed::Missing
+    } else {
+        let output = Command::new(program, args).read_unchecked(exec);
+        let found = output
+            .ok()
+            .as_ref()
+            .and_then(|output| parse_version(output));
+
+        match found {
+            Some(ref found) if found == required => Installed::Current,
+            found => Installed::Outdated(found),
+        }
+    }
+}
+
+/// The program mav installed to `prefix` if there is one, or else the one on `PATH`.
+pub fn locate(prefix: &Path, cmd: &str) -> OsString {
+    let in_prefix = prefix.join(cmd);
+
+    if in_prefix.is_file() {
+        in_prefix.into_os_string()
+    } else {
+        cmd.into()
+    }
+}
+
+/// Picks the first `x.y.z` out of a program's version output, such as `Client Version: v1.15.3`
+/// or `SemVer:"v2.14.3"`.
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .find(|word| {
+            let parts: Vec<_> = word.split('.').collect();
+            parts.len() == 3 && parts.iter().all(|part| !part.is_empty())
+        })
+        .map(ToOwned::to_owned)
+}
+
+pub fn install_brew(exec: &dyn Executor) -> Result<()> {
+    if !check_install(exec, "brew") {
         println!("Installing brew..");
 
         let script = Command::new(
@@ -147,36 +616,37 @@ pub fn install_brew() {
                 "https://raw.githubusercontent.com/Homebrew/install/master/install",
             ],
         )
-        .read();
+        .read(exec)?;
 
-        Command::new("ruby", vec!["-e", &script]).run();
+        Command::new("ruby", vec!["-e", &script]).run(exec)?;
 
         println!("Homebrew {}", "is installed successfully.".green());
     }
+
+    Ok(())
 }
 
-pub fn install_brew_formula<F: AsRef<OsStr> + Clone + fmt::Display>(formula: F) {
-    install_brew_formulae(vec![formula])
+pub fn install_brew_formula(exec: &dyn Executor, formula: &str) -> Result<()> {
+    install_brew_formulae(exec, vec![formula])
 }
 
-pub fn install_brew_formulae<F, I>(formulae: F)
+pub fn install_brew_formulae<'f, F>(exec: &dyn Executor, formulae: F) -> Result<()>
 where
-    F: IntoIterator<Item = I>,
-    I: AsRef<OsStr> + Clone + fmt::Display,
+    F: IntoIterator<Item = &'f str>,
 {
     let mut formulae_to_install = vec![];
-    let mut args = vec!["install".into()];
+    let mut args = vec!["install"];
 
     for formula in formulae {
-        if !check_install(&formula) {
-            formulae_to_install.push(formula.clone());
-            args.push(formula.as_ref().to_owned());
+        if !check_install(exec, formula) {
+            formulae_to_install.push(formula);
+            args.push(formula);
         }
     }
 
     if args.len() > 1 {
-        install_brew();
-        Command::new("brew", args).run();
+        install_brew(exec)?;
+        Command::new("brew", args).run(exec)?;
 
         for formula in formulae_to_install {
             println!(
@@ -187,149 +657,1020 @@ where
             );
         }
     }
+
+    Ok(())
 }
 
-pub fn install_by_downloading<C, U>(cmd: C, url: U) -> DownloadingInstaller
-where
-    C: Into<String>,
-    U: Into<String>,
-{
-    DownloadingInstaller::new().enqueue(cmd, url)
+pub fn is_on_path(dir: &Path) -> bool {
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|path| path == dir),
+        None => false,
+    }
 }
 
-#[derive(Debug)]
-pub struct DownloadingInstaller {
+#[derive(Clone, Debug, PartialEq)]
+pub enum Checksum {
+    /// A pinned hex-encoded SHA-256 digest.
+    Sha256(String),
+    /// A URL to a checksum file the project publishes, listing the digest of `file`.
+    Url { url: String, file: String },
+    /// The project publishes no checksums, so the download cannot be verified.
+    Unpublished,
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Checksum::Sha256(digest) => write!(f, "SHA-256 {}", digest),
+            Checksum::Url { url, .. } => write!(f, "{}", url),
+            Checksum::Unpublished => write!(f, "no published checksum"),
+        }
+    }
+}
+
+impl Checksum {
+    /// Points to the `<url>.sha256` file published next to a download.
+    pub fn sidecar_of(url: &str) -> Self {
+        Checksum::Url {
+            url: format!("{}.sha256", url),
+            file: file_name(url).into(),
+        }
+    }
+
+    /// The digest pinned for the download's file name if there is one, or else `published`.
+    pub fn pinned_or(pins: &BTreeMap<String, String>, url: &str, published: Checksum) -> Self {
+        match pins.get(file_name(url)) {
+            Some(digest) => Checksum::Sha256(digest.to_lowercase()),
+            None => published,
+        }
+    }
+
+    async fn resolve(self) -> std::result::Result<String, Interruption> {
+        match self {
+            Checksum::Sha256(digest) => Ok(digest.to_lowercase()),
+
+            Checksum::Url { url, file } => {
+                let res = Client::new().get(&url).send().compat().await;
+                let mut res = res.and_then(|res| res.error_for_status()).map_err(|err| {
+                    Interruption::from(err)
+                        .map(|reason| format!("checksum unavailable: {}", reason))
+                })?;
+
+                let text = res.text().compat().await?;
+
+                parse_checksum_file(&text, &file).ok_or_else(|| {
+                    Interruption::Permanent(format!("checksum file does not list {}", file))
+                })
+            }
+
+            Checksum::Unpublished => Err(Interruption::Permanent(
+                "no published checksum, pin its SHA-256 under [install.sha256]".into(),
+            )),
+        }
+    }
+}
+
+fn file_name(url: &str) -> &str {
+    url.rsplit('/').next().unwrap_or(url)
+}
+
+/// Finds the digest of `file` in a checksum file, which holds either the bare digest or
+/// `<digest>  <file>` lines like `sha256sum` prints.
+fn parse_checksum_file(text: &str, file: &str) -> Option<String> {
+    text.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some(digest), None) => Some(digest.to_lowercase()),
+            (Some(digest), Some(name)) if name.trim_start_matches('*') == file => {
+                Some(digest.to_lowercase())
+            }
+            _ => None,
+        }
+    })
+}
+
+pub struct DownloadingInstaller<'a> {
+    exec: &'a dyn Executor,
+    /// The directory downloaded programs are installed to.
+    prefix: PathBuf,
+    versions: Versions,
+    /// Only reinstalls programs installed at another version than required.
+    upgrade: bool,
+    /// Where downloads are kept until installed, private to this run.
+    staging: PathBuf,
     items: Vec<DownloadedItem>,
 }
 
 #[derive(Debug)]
 struct DownloadedItem {
     cmd: String,
+    /// The version required of the program, which names it in the download cache.
+    version: Option<String>,
     url: String,
+    checksum: Checksum,
+    /// The program's path inside the downloaded `.tar.gz`, if the download is an archive.
+    member: Option<String>,
+    /// Installs the download instead of mav.
     postinstall: Option<Command>,
 }
 
 impl DownloadedItem {
-    pub fn new(cmd: String, url: String, postinstall: Option<Command>) -> Self {
+    pub fn new(
+        cmd: String,
+        version: Option<String>,
+        url: String,
+        checksum: Checksum,
+        member: Option<String>,
+        postinstall: Option<Command>,
+    ) -> Self {
         DownloadedItem {
             cmd,
+            version,
             url,
+            checksum,
+            member,
             postinstall,
         }
     }
 }
 
+impl DownloadedItem {
+    /// Names the download in the cache by the program and version it holds, so that reinstalling
+    /// finds it without asking where it came from.
+    fn cache_key(&self) -> String {
+        let version = self.version.as_ref().map_or("unversioned", String::as_str);
+        format!("{}-{}", self.cmd, version)
+    }
+}
+
 impl Clone for DownloadedItem {
     fn clone(&self) -> Self {
-        DownloadedItem::new(self.cmd.clone(), self.url.clone(), self.postinstall.clone())
+        DownloadedItem::new(
+            self.cmd.clone(),
+            self.version.clone(),
+            self.url.clone(),
+            self.checksum.clone(),
+            self.member.clone(),
+            self.postinstall.clone(),
+        )
     }
 }
 
-impl DownloadingInstaller {
-    pub fn new() -> Self {
-        DownloadingInstaller { items: vec![] }
+impl<'a> DownloadingInstaller<'a> {
+    pub fn new(exec: &'a dyn Executor, prefix: &Path, versions: &Versions) -> Self {
+        DownloadingInstaller {
+            exec,
+            prefix: prefix.to_owned(),
+            versions: versions.clone(),
+            upgrade: false,
+            staging: env::temp_dir().join(format!("mav-install-{}", process::id())),
+            items: vec![],
+        }
     }
 
-    pub fn enqueue<C, U>(self, cmd: C, url: U) -> Self
+    /// Makes the installer skip missing programs and replace outdated ones.
+    pub fn upgrading(self) -> Self {
+        DownloadingInstaller {
+            upgrade: true,
+            ..self
+        }
+    }
+
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Where a download is kept, named after its program, until it is installed.
+    pub fn staged(&self, cmd: &str) -> PathBuf {
+        self.staging.join(cmd)
+    }
+
+    pub fn enqueue<C, U>(self, cmd: C, url: U, checksum: Checksum) -> Self
+    where
+        C: Into<String>,
+        U: Into<String>,
+    {
+        self.enqueue_item(cmd.into(), url.into(), checksum, None, None)
+    }
+
+    /// Enqueues a `.tar.gz` download holding the program at `member`.
+    pub fn enqueue_archive<C, U, M>(self, cmd: C, url: U, checksum: Checksum, member: M) -> Self
     where
         C: Into<String>,
         U: Into<String>,
+        M: Into<String>,
     {
-        self.enqueue_with_postinstall(cmd, url, None)
+        self.enqueue_item(cmd.into(), url.into(), checksum, Some(member.into()), None)
     }
 
-    pub fn enqueue_with_postinstall<C, U, P>(mut self, cmd: C, url: U, postinstall: P) -> Self
+    pub fn enqueue_with_postinstall<C, U, P>(
+        self,
+        cmd: C,
+        url: U,
+        checksum: Checksum,
+        postinstall: P,
+    ) -> Self
     where
         C: Into<String>,
         U: Into<String>,
         P: Into<Option<Command>>,
     {
-        let cmd = cmd.into();
+        self.enqueue_item(cmd.into(), url.into(), checksum, None, postinstall.into())
+    }
 
-        if !check_install(&cmd) {
-            let item = DownloadedItem::new(cmd, url.into(), postinstall.into());
+    fn enqueue_item(
+        mut self,
+        cmd: String,
+        url: String,
+        checksum: Checksum,
+        member: Option<String>,
+        postinstall: Option<Command>,
+    ) -> Self {
+        let installed = match self.versions.get(&cmd) {
+            Some(required) => check_version(self.exec, locate(&self.prefix, &cmd), &cmd, required),
+            None if self.prefix.join(&cmd).is_file() || check_install(self.exec, &cmd) => {
+                Installed::Current
+            }
+            None => Installed::Missing,
+        };
+
+        let wanted = match installed {
+            Installed::Missing => !self.upgrade,
+            Installed::Outdated(_) => true,
+            Installed::Current => false,
+        };
+
+        if wanted {
+            let version = self.versions.get(&cmd).map(ToOwned::to_owned);
+            let item = DownloadedItem::new(cmd, version, url, checksum, member, postinstall);
             self.items.push(item);
         }
 
         self
     }
 
-    pub fn run(self) {
-        tokio_run(async move {
+    pub fn run(self) -> Result<()> {
+        if is_dry_run() {
+            for item in self.items {
+                println!(
+                    "{} {} {} {}",
+                    "Would download".cyan(),
+                    item.url,
+                    "verified against".cyan(),
+                    item.checksum,
+                );
+
+                match item.postinstall {
+                    // Downloading would fail before anything gets installed.
+                    _ if item.checksum == Checksum::Unpublished => println!(
+                        "{} {} {}",
+                        "Would fail:".red(),
+                        item.cmd,
+                        "has no published checksum, pin its SHA-256 under [install.sha256]".red(),
+                    ),
+                    Some(postinstall) => postinstall.run(self.exec)?,
+                    None => println!(
+                        "{} {} {} {}",
+                        "Would install".cyan(),
+                        item.cmd,
+                        "to".cyan(),
+                        self.prefix.join(&item.cmd).display(),
+                    ),
+                }
+            }
+
+            return Ok(());
+        }
+
+        let items = self.items.clone();
+        let staging = self.staging.clone();
+        fs::create_dir_all(&staging)?;
+
+        let (mut failed, restored) = tokio_run(async move {
             let progress = MultiProgress::new();
 
             let style = ProgressStyle::default_bar()
                 .template("{spinner:.green} {msg:10} [{bar:40.cyan/blue}] {bytes}/{total_bytes} (ETA: {eta})")
                 .progress_chars("#>-");
 
-            let mut items = vec![];
+            let failed_style = ProgressStyle::default_bar().template("{msg:.red}");
+
+            let mut downloads = vec![];
 
-            for item in &self.items {
+            for item in items {
                 let bar = ProgressBar::new(1);
                 let bar = progress.add(bar);
                 bar.set_style(style.clone());
                 bar.set_message(&item.cmd[..cmp::min(10, item.cmd.len())]);
 
-                items.push((item.to_owned(), bar));
+                downloads.push((item, bar));
             }
 
+            let failed = Arc::new(Mutex::new(vec![]));
+            let failed_in_downloads = failed.clone();
+            let restored = Arc::new(Mutex::new(vec![]));
+            let restored_in_downloads = restored.clone();
+
             tokio_spawn(
-                stream::iter(items).for_each_concurrent(4, async move |(item, bar)| {
-                    let res = Client::new().get(&item.url).send().compat().await.unwrap();
-                    let total_size = res.content_length().unwrap_or_default();
+                stream::iter(downloads).for_each_concurrent(4, move |(item, bar)| {
+                    let failed = failed_in_downloads.clone();
+                    let restored = restored_in_downloads.clone();
+                    let failed_style = failed_style.clone();
+                    let staging = staging.clone();
 
-                    bar.set_length(total_size);
+                    async move {
+                        let cmd = item.cmd.clone();
 
-                    let mut body = res.into_body().compat();
-                    let mut file = File::create(item.cmd.clone()).compat().await.unwrap();
+                        match download(item, &staging, &bar).await {
+                            Ok(false) => bar.finish_and_clear(),
 
-                    let mut downloaded_size = 0u64;
+                            Ok(true) => {
+                                bar.finish_and_clear();
+                                restored.lock().unwrap().push(cmd);
+                            }
 
-                    while let Some(Ok(chunk)) = body.next().await {
-                        downloaded_size += chunk.len() as u64;
-                        file = tokio::io::write_all(file, chunk).compat().await.unwrap().0;
-                        bar.set_position(downloaded_size);
+                            Err(reason) => {
+                                // Leave the failure on screen while the other downloads go on.
+                                bar.set_style(failed_style);
+                                bar.finish_with_message(&format!("✘ {}: {}", cmd, reason));
+                                failed.lock().unwrap().push((cmd, reason));
+                            }
+                        }
                     }
+                }),
+            );
 
-                    if let Some(postinstall) = item.postinstall {
-                        postinstall.run();
-                    } else {
-                        fs::set_permissions(&item.cmd, fs::Permissions::from_mode(0o755)).unwrap();
-                        fs::rename(&item.cmd, format!("/usr/local/bin/{}", item.cmd)).unwrap();
-                    }
+            let _ = progress.join();
 
-                    bar.finish_and_clear();
-                }),
+            let failed = failed.lock().unwrap().clone();
+            let restored = restored.lock().unwrap().clone();
+            (failed, restored)
+        });
+
+        for cmd in restored {
+            println!(
+                "{} {}",
+                cmd,
+                "is restored from the download cache.".yellow()
             );
+        }
+
+        // Installing may run commands, so it happens here rather than on the download tasks.
+        for item in self.items {
+            if failed.iter().any(|(cmd, _)| cmd == &item.cmd) {
+                continue;
+            }
 
-            progress.join_and_clear().unwrap();
+            let cmd = item.cmd.clone();
 
-            for item in &self.items {
-                println!("{} {}", item.cmd, "is installed successfully.".green());
+            match install(self.exec, &self.prefix, &self.staging, item) {
+                Ok(()) => println!("{} {}", cmd, "is installed successfully.".green()),
+                Err(err) => failed.push((cmd, err.to_string())),
             }
-        });
+        }
+
+        let _ = fs::remove_dir_all(&self.staging);
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DownloadFailed(failed))
+        }
+    }
+}
+
+/// Why a download attempt stopped short.
+enum Interruption {
+    /// Worth another attempt, resuming from what is already on disk.
+    Transient(String),
+    Permanent(String),
+}
+
+impl Interruption {
+    fn map<F: FnOnce(String) -> String>(self, describe: F) -> Self {
+        match self {
+            Interruption::Transient(reason) => Interruption::Transient(describe(reason)),
+            Interruption::Permanent(reason) => Interruption::Permanent(describe(reason)),
+        }
+    }
+}
+
+impl From<reqwest::Error> for Interruption {
+    fn from(err: reqwest::Error) -> Self {
+        match err.status() {
+            Some(status) if status.is_client_error() => Interruption::Permanent(err.to_string()),
+            _ => Interruption::Transient(err.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for Interruption {
+    fn from(err: io::Error) -> Self {
+        Interruption::Permanent(err.to_string())
     }
 }
 
-#[derive(PartialEq, Debug)]
-pub enum MinikubeStatus {
+/// Downloads an item to `<cmd>.part` in the staging directory, retrying with exponential backoff,
+/// and keeps it as `<cmd>` only when its digest matches. Returns whether the download cache had it
+/// already.
+async fn download(
+    item: DownloadedItem,
+    staging: &Path,
+    bar: &ProgressBar,
+) -> std::result::Result<bool, String> {
+    let partial = staging.join(format!("{}.part", item.cmd));
+    let mut expected_digest = None;
+    let mut backoff = DOWNLOAD_BACKOFF;
+    let mut restored = false;
+
+    for attempt in 1..=DOWNLOAD_ATTEMPTS {
+        let interruption = match download_attempt(&item, &partial, &mut expected_digest, bar).await
+        {
+            Ok(from_cache) => {
+                restored = from_cache;
+                break;
+            }
+            Err(interruption) => interruption,
+        };
+
+        match interruption {
+            Interruption::Transient(reason) if attempt < DOWNLOAD_ATTEMPTS => {
+                bar.set_message(&format!("{} (retry {}: {})", item.cmd, attempt, reason));
+
+                Delay::new(Instant::now() + backoff)
+                    .compat()
+                    .await
+                    .map_err(|err| err.to_string())?;
+
+                backoff *= 2;
+            }
+
+            Interruption::Transient(reason) | Interruption::Permanent(reason) => {
+                return Err(reason)
+            }
+        }
+    }
+
+    let digest = cache::sha256_of(&partial).map_err(|err| err.to_string())?;
+
+    if Some(&digest) != expected_digest.as_ref() {
+        let _ = fs::remove_file(&partial);
+        return Err("checksum mismatch".into());
+    }
+
+    if !restored {
+        // The cache only saves time, so failing to fill it does not fail the install.
+        let _ = cache::store(&item.cache_key(), &item.url, &partial, &digest);
+    }
+
+    fs::rename(&partial, staging.join(&item.cmd)).map_err(|err| err.to_string())?;
+
+    Ok(restored)
+}
+
+/// Fetches what is missing from the partial file, resuming with an HTTP range when possible.
+///
+/// The first attempt looks in the download cache before anything goes online, checksum files
+/// included, and returns whether it restored the partial file from there instead.
+async fn download_attempt(
+    item: &DownloadedItem,
+    partial: &Path,
+    expected_digest: &mut Option<String>,
+    bar: &ProgressBar,
+) -> std::result::Result<bool, Interruption> {
+    if expected_digest.is_none() {
+        let pinned = match &item.checksum {
+            Checksum::Sha256(digest) => Some(digest.as_str()),
+            _ => None,
+        };
+
+        if let Some(digest) = cache::restore(&item.cache_key(), &item.url, pinned, partial) {
+            *expected_digest = Some(digest);
+            return Ok(true);
+        }
+
+        *expected_digest = Some(item.checksum.clone().resolve().await?);
+    }
+
+    let offset = fs::metadata(partial).map(|meta| meta.len()).unwrap_or(0);
+    let mut req = Client::new().get(&item.url);
+
+    if offset > 0 {
+        req = req.header(RANGE, format!("bytes={}-", offset));
+    }
+
+    let res = req.send().compat().await?;
+
+    if res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        fs::remove_file(partial)?;
+        return Err(Interruption::Transient("cannot resume".into()));
+    }
+
+    let res = res.error_for_status()?;
+
+    // Servers that ignore the range send the whole file again.
+    let resumed = res.status() == StatusCode::PARTIAL_CONTENT;
+    let mut size = if resumed { offset } else { 0 };
+    let expected_size = res.content_length().map(|len| size + len);
+
+    bar.set_length(expected_size.unwrap_or_default());
+    bar.set_position(size);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial.to_owned())
+        .compat()
+        .await?;
+
+    let mut body = res.into_body().compat();
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        size += chunk.len() as u64;
+        file = tokio::io::write_all(file, chunk).compat().await?.0;
+        bar.set_position(size);
+    }
+
+    match expected_size {
+        Some(expected_size) if size != expected_size => Err(Interruption::Transient(format!(
+            "got {} of {} bytes",
+            size, expected_size
+        ))),
+        _ => Ok(false),
+    }
+}
+
+/// Installs a download from the staging directory, which the installer removes afterwards along
+/// with anything extracted there.
+fn install(exec: &dyn Executor, prefix: &Path, staging: &Path, item: DownloadedItem) -> Result<()> {
+    if let Some(postinstall) = item.postinstall {
+        return postinstall.run(exec);
+    }
+
+    let program = match &item.member {
+        Some(member) => {
+            Command::new(
+                "tar",
+                vec![
+                    "-xzf".as_ref(),
+                    staging.join(&item.cmd).as_os_str(),
+                    "-C".as_ref(),
+                    staging.as_os_str(),
+                    member.as_ref(),
+                ],
+            )
+            .run(exec)?;
+
+            staging.join(member)
+        }
+        None => staging.join(&item.cmd),
+    };
+
+    install_program(&program, prefix, &item.cmd)
+}
+
+/// Copies a program next to its destination and renames it into place, so that the prefix never
+/// holds a half-written program and the copy works across filesystems.
+fn install_program(program: &Path, prefix: &Path, name: &str) -> Result<()> {
+    fs::create_dir_all(prefix)?;
+
+    let temp = prefix.join(format!(".{}.tmp", name));
+
+    fs::copy(program, &temp)?;
+    fs::set_permissions(&temp, fs::Permissions::from_mode(0o755))?;
+    fs::rename(&temp, prefix.join(name))?;
+
+    Ok(())
+}
+
+/// The state of one Minikube component.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MinikubeState {
     Running,
     Stopped,
+    Configured,
+    Misconfigured,
+    /// Not created, or a state mav does not know.
     Unknown,
 }
 
-pub fn get_minikube_status() -> MinikubeStatus {
-    let status = Command::new("minikube", vec!["--profile=mav", "status"]).read_unchecked();
-    let status = status.lines().next().unwrap_or_default();
-    let status = status.split_whitespace().take(2).last().unwrap_or_default();
+impl MinikubeState {
+    /// Reads a state, ignoring details after it like the `: pointing to minikube-vm at …` that
+    /// Minikube 1.3 adds to the kubectl state.
+    fn from_name(name: &str) -> Self {
+        match name.split(':').next().unwrap_or_default().trim() {
+            "Running" => MinikubeState::Running,
+            "Stopped" => MinikubeState::Stopped,
+            "Configured" | "Correctly Configured" => MinikubeState::Configured,
+            "Misconfigured" => MinikubeState::Misconfigured,
+            _ => MinikubeState::Unknown,
+        }
+    }
+}
 
-    match status {
-        "Running" => MinikubeStatus::Running,
-        "Stopped" => MinikubeStatus::Stopped,
-        _ => MinikubeStatus::Unknown,
+impl fmt::Display for MinikubeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MinikubeState::Running => "Running",
+            MinikubeState::Stopped => "Stopped",
+            MinikubeState::Configured => "Configured",
+            MinikubeState::Misconfigured => "Misconfigured",
+            MinikubeState::Unknown => "Unknown",
+        };
+
+        write!(f, "{}", name)
     }
 }
 
-pub fn get_minikube_ip() -> String {
-    Command::new("minikube", vec!["--profile=mav", "ip"]).read()
+/// What `minikube status` reports for each component of the machine.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MinikubeStatus {
+    pub host: MinikubeState,
+    pub kubelet: MinikubeState,
+    pub apiserver: MinikubeState,
+    pub kubeconfig: MinikubeState,
+}
+
+impl MinikubeStatus {
+    /// Reads `minikube status --output=json`, or the `component: state` lines older Minikube
+    /// versions print. Fields that are not states, like `"Worker": false`, are skipped.
+    fn parse(output: &str) -> Self {
+        let states: Vec<(String, String)> = match serde_json::from_str(output) {
+            Ok(serde_json::Value::Object(fields)) => fields
+                .into_iter()
+                .filter_map(|(name, value)| Some((name, value.as_str()?.to_string())))
+                .collect(),
+            _ => output
+                .lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(2, ':');
+                    Some((parts.next()?.to_string(), parts.next()?.to_string()))
+                })
+                .collect(),
+        };
+
+        // Minikube 1.3 calls the kubeconfig state `kubectl`.
+        let component = |name: &str| match name.trim().to_lowercase().as_str() {
+            "kubectl" => "kubeconfig".to_string(),
+            name => name.to_string(),
+        };
+
+        let state = |wanted: &str| {
+            states
+                .iter()
+                .find(|(name, _)| component(name) == wanted)
+                .map_or(MinikubeState::Unknown, |(_, state)| {
+                    MinikubeState::from_name(state)
+                })
+        };
+
+        MinikubeStatus {
+            host: state("host"),
+            kubelet: state("kubelet"),
+            apiserver: state("apiserver"),
+            kubeconfig: state("kubeconfig"),
+        }
+    }
+
+    /// The status of a machine that does not exist.
+    pub fn unknown() -> Self {
+        MinikubeStatus {
+            host: MinikubeState::Unknown,
+            kubelet: MinikubeState::Unknown,
+            apiserver: MinikubeState::Unknown,
+            kubeconfig: MinikubeState::Unknown,
+        }
+    }
+
+    /// Whether the machine exists, running or not.
+    pub fn is_created(&self) -> bool {
+        self.host != MinikubeState::Unknown
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.host == MinikubeState::Running
+    }
+
+    /// Whether Kubernetes runs in the machine and kubectl points to it.
+    pub fn is_healthy(&self) -> bool {
+        self.is_running()
+            && self.kubelet == MinikubeState::Running
+            && self.apiserver == MinikubeState::Running
+            && self.kubeconfig == MinikubeState::Configured
+    }
+}
+
+impl fmt::Display for MinikubeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "host {}, kubelet {}, apiserver {}, kubeconfig {}",
+            self.host, self.kubelet, self.apiserver, self.kubeconfig,
+        )
+    }
+}
+
+pub fn get_minikube_status(exec: &dyn Executor, profile: &str) -> Result<MinikubeStatus> {
+    // Stopped machines make it fail, with the JSON still on stdout.
+    let json = Command::minikube(profile, vec!["status", "--output=json"]).probe_unchecked(exec)?;
+
+    if json.trim_start().starts_with('{') {
+        return Ok(MinikubeStatus::parse(&json));
+    }
+
+    // Older Minikube versions reject `--output` and print nothing on stdout.
+    let text = Command::minikube(profile, vec!["status"]).read_unchecked(exec)?;
+
+    Ok(MinikubeStatus::parse(&text))
+}
+
+/// What Minikube created the machine with, as its profile records it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MinikubeMachine {
+    pub driver: String,
+    pub cpus: u16,
+    /// In megabytes.
+    pub memory: u64,
+    /// In megabytes.
+    pub disk_size: u64,
+}
+
+/// Where Minikube keeps profiles and machines, following `MINIKUBE_HOME` like Minikube does.
+fn minikube_dir() -> PathBuf {
+    let home = match env::var_os("MINIKUBE_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => dirs::home_dir().unwrap_or_default(),
+    };
+
+    if home.ends_with(".minikube") {
+        home
+    } else {
+        home.join(".minikube")
+    }
+}
+
+fn minikube_profiles_dir(exec: &dyn Executor) -> PathBuf {
+    exec.minikube_dir().join("profiles")
+}
+
+/// The profiles Minikube has created machines for, sorted by name.
+pub fn get_minikube_profiles(exec: &dyn Executor) -> Result<Vec<String>> {
+    let dir = minikube_profiles_dir(exec);
+
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut profiles = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.join("config.json").is_file() {
+            profiles.extend(
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned()),
+            );
+        }
+    }
+
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Reads the profile of the machine, or nothing when Minikube has not created one.
+pub fn get_minikube_machine(exec: &dyn Executor, profile: &str) -> Result<Option<MinikubeMachine>> {
+    let path = minikube_profiles_dir(exec)
+        .join(profile)
+        .join("config.json");
+
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let profile: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|err| Error::InvalidConfig(format!("{}: {}", path.display(), err)))?;
+    let machine = &profile["MachineConfig"];
+
+    // Minikube renamed `VMDriver` to `Driver` in later versions.
+    let driver = machine["VMDriver"]
+        .as_str()
+        .or_else(|| machine["Driver"].as_str())
+        .unwrap_or_default();
+
+    Ok(Some(MinikubeMachine {
+        driver: driver.into(),
+        cpus: machine["CPUs"].as_u64().unwrap_or_default() as u16,
+        memory: machine["Memory"].as_u64().unwrap_or_default(),
+        disk_size: machine["DiskSize"].as_u64().unwrap_or_default(),
+    }))
+}
+
+/// Records resized CPUs and memory in the profile, where Minikube and `get_minikube_machine`
+/// read them.
+pub fn set_minikube_resources(
+    exec: &dyn Executor,
+    profile: &str,
+    cpus: u16,
+    memory: u64,
+) -> Result<()> {
+    let path = minikube_profiles_dir(exec)
+        .join(profile)
+        .join("config.json");
+
+    update_json_file(&path, |profile| {
+        profile["MachineConfig"]["CPUs"] = cpus.into();
+        profile["MachineConfig"]["Memory"] = memory.into();
+    })
+}
+
+/// Changes a JSON file that Minikube owns, unless commands are only being printed.
+pub fn update_json_file<F: FnOnce(&mut serde_json::Value)>(path: &Path, update: F) -> Result<()> {
+    if is_dry_run() {
+        println!("{} {}", "Would update".cyan(), path.display());
+        return Ok(());
+    }
+
+    let mut json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|err| Error::InvalidConfig(format!("{}: {}", path.display(), err)))?;
+
+    update(&mut json);
+    fs::write(path, serde_json::to_string_pretty(&json).unwrap())?;
+
+    Ok(())
+}
+
+/// Reads a size like `2000mb` or `20g` in megabytes, the unit Minikube assumes without one.
+pub fn parse_megabytes(size: &str) -> Option<u64> {
+    let size = size.trim().to_lowercase();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: u64 = number.parse().ok()?;
+
+    match unit.trim_end_matches('b') {
+        "k" => Some(number / 1024),
+        "" | "m" => Some(number),
+        "g" => Some(number * 1024),
+        _ => None,
+    }
+}
+
+pub fn get_minikube_ip(exec: &dyn Executor, profile: &str) -> Result<String> {
+    Command::minikube(profile, vec!["ip"]).read(exec)
+}
+
+/// Whether each addon Minikube knows is enabled in the machine.
+pub fn get_minikube_addons(exec: &dyn Executor, profile: &str) -> Result<BTreeMap<String, bool>> {
+    let json = Command::minikube(profile, vec!["addons", "list", "--output=json"])
+        .probe_unchecked(exec)?;
+
+    if let Ok(addons) = serde_json::from_str::<BTreeMap<String, serde_json::Value>>(&json) {
+        return Ok(addons
+            .into_iter()
+            .map(|(name, addon)| (name, addon["Status"] == "enabled"))
+            .collect());
+    }
+
+    // Older Minikube versions print `- name: enabled` lines instead.
+    let text = Command::minikube(profile, vec!["addons", "list"]).read(exec)?;
+
+    Ok(text
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim_start_matches("- ").splitn(2, ':');
+            let name = parts.next()?.trim();
+            let state = parts.next()?.trim();
+
+            Some((name.to_string(), state == "enabled"))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions_of_managed_programs() {
+        let outputs = [
+            ("Client Version: v1.15.3", "1.15.3"),
+            (
+                "minikube version: v1.3.1\ncommit: ca60a424ce69a4d79f502650199ca2b52f29e631",
+                "1.3.1",
+            ),
+            (
+                "Client: &version.Version{SemVer:\"v2.14.3\", GitCommit:\"0e7f3b6\"}",
+                "2.14.3",
+            ),
+            ("helmfile version v0.82.0", "0.82.0"),
+        ];
+
+        for (output, version) in &outputs {
+            assert_eq!(parse_version(output), Some(version.to_string()));
+        }
+    }
+
+    #[test]
+    fn reports_missing_programs_without_running_them() {
+        let exec = ScriptedExecutor::new().without("helmfile").answer(
+            "kubectl version",
+            0,
+            "Client Version: v1.14.0",
+        );
+
+        assert_eq!(
+            installed_version(&exec, "helmfile", "helmfile", "0.82.0"),
+            Installed::Missing,
+        );
+
+        assert_eq!(
+            installed_version(&exec, "kubectl", "kubectl", "1.15.3"),
+            Installed::Outdated(Some("1.14.0".into())),
+        );
+
+        assert_eq!(
+            *exec.calls.borrow(),
+            vec!["kubectl version --client --short"]
+        );
+    }
+
+    #[test]
+    fn reads_digests_from_published_checksum_files() {
+        let digest = "a2ff5bfb3b3c21b0cc6b6a1e1c0d4b1d2c1fe4f0e6e0d4b2c4d5a3d1c0f9e8d7";
+
+        assert_eq!(
+            parse_checksum_file(&format!("{}\n", digest), "kubectl"),
+            Some(digest.to_string()),
+        );
+
+        let listing = format!(
+            "{}  helm-v3.0.2-darwin-amd64.tar.gz\n{}  helm-v3.0.2-linux-amd64.tar.gz\n",
+            "0".repeat(64),
+            digest.to_uppercase(),
+        );
+
+        assert_eq!(
+            parse_checksum_file(&listing, "helm-v3.0.2-linux-amd64.tar.gz"),
+            Some(digest.to_string()),
+        );
+
+        assert_eq!(parse_checksum_file(&listing, "helmfile_linux_amd64"), None);
+    }
+
+    #[test]
+    fn runs_pipes_within_a_step() {
+        let exec = ScriptedExecutor::new().answer("ip route show | grep default", 0, "default");
+
+        let output = Command::new("ip", vec!["route", "show"])
+            .pipe("grep", vec!["default"])
+            .then("true", Vec::<&str>::new())
+            .read(&exec)
+            .unwrap();
+
+        assert_eq!(output, "default");
+        assert_eq!(
+            *exec.calls.borrow(),
+            vec!["ip route show | grep default", "true"],
+        );
+    }
+
+    #[test]
+    fn parses_sizes_in_megabytes() {
+        assert_eq!(parse_megabytes("2000mb"), Some(2000));
+        assert_eq!(parse_megabytes("2000"), Some(2000));
+        assert_eq!(parse_megabytes("20g"), Some(20480));
+        assert_eq!(parse_megabytes("2GB"), Some(2048));
+        assert_eq!(parse_megabytes("lots"), None);
+    }
+
+    #[test]
+    fn parses_minikube_status_of_old_and_current_versions() {
+        let healthy = MinikubeStatus {
+            host: MinikubeState::Running,
+            kubelet: MinikubeState::Running,
+            apiserver: MinikubeState::Running,
+            kubeconfig: MinikubeState::Configured,
+        };
+
+        // Minikube 1.3.1
+        let text = "host: Running\nkubelet: Running\napiserver: Running\n\
+                    kubectl: Correctly Configured: pointing to minikube-vm at 192.168.99.100\n";
+        assert_eq!(MinikubeStatus::parse(text), healthy);
+        assert!(MinikubeStatus::parse(text).is_healthy());
+
+        let json = r#"{"Name":"mav","Host":"Running","Kubelet":"Running","APIServer":"Running","Kubeconfig":"Configured","Worker":false}"#;
+        assert_eq!(MinikubeStatus::parse(json), healthy);
+
+        let text = "host: Running\nkubelet: Running\napiserver: Running\nkubectl: Misconfigured: pointing to stale minikube-vm.\n";
+        assert_eq!(
+            MinikubeStatus::parse(text).kubeconfig,
+            MinikubeState::Misconfigured,
+        );
+    }
 }